@@ -0,0 +1,215 @@
+//! Git backends. Everything that touches a repo's history during install and
+//! update goes through [`GitBackend`], so the same commands can run on the
+//! `git` binary or in-process via gitoxide.
+
+mod gitoxide;
+
+use crate::config::Config;
+use crate::git;
+use crate::meta::CloneStrategy;
+use anyhow::{bail, Result};
+use serde::Deserialize;
+use std::path::Path;
+use std::str::FromStr;
+use std::sync::OnceLock;
+
+/// Overrides the `git_backend` setting for a single run.
+const BACKEND_ENV: &str = "DOTHUB_GIT_BACKEND";
+
+/// Which implementation of [`GitBackend`] to use.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BackendKind {
+    /// Shell out to the `git` binary
+    #[default]
+    Cli,
+    /// Pure-Rust gitoxide, no `git` binary required
+    Gix,
+}
+
+impl FromStr for BackendKind {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "cli" => Ok(BackendKind::Cli),
+            "gix" => Ok(BackendKind::Gix),
+            other => bail!("Unknown git backend '{}' (expected cli or gix)", other),
+        }
+    }
+}
+
+/// Moving a branch forward to its upstream.
+#[derive(Debug, Clone)]
+pub struct FastForward {
+    pub from: String,
+    pub to: String,
+    /// Paths touched between `from` and `to`
+    pub changed_files: Vec<String>,
+}
+
+impl FastForward {
+    pub fn is_noop(&self) -> bool {
+        self.from == self.to
+    }
+}
+
+/// Snapshot of a repo's worktree.
+#[derive(Debug, Clone)]
+pub struct RepoStatus {
+    /// Checked out branch; `None` when HEAD is detached
+    pub branch: Option<String>,
+    /// Tracked files differ from HEAD
+    pub dirty: bool,
+}
+
+pub trait GitBackend: Send + Sync {
+    fn name(&self) -> &'static str;
+
+    /// Fail early when the backend cannot work on this machine.
+    fn ensure_available(&self) -> Result<()>;
+
+    /// Clone `url` into `dest`, checking out the remote's default branch.
+    fn clone_repo(&self, url: &str, dest: &Path, strategy: &CloneStrategy) -> Result<()>;
+
+    /// Fetch new commits and tags from `origin` without touching the worktree.
+    fn fetch(&self, repo: &Path) -> Result<()>;
+
+    /// Commit the checked out branch's upstream points at, as of the last fetch.
+    fn upstream_commit(&self, repo: &Path) -> Result<String>;
+
+    /// Fast-forward the checked out branch to its already fetched upstream.
+    fn fast_forward(&self, repo: &Path) -> Result<FastForward>;
+
+    fn status(&self, repo: &Path) -> Result<RepoStatus>;
+
+    /// Commits HEAD has that its upstream lacks, and the other way round.
+    fn ahead_behind(&self, repo: &Path) -> Result<(usize, usize)>;
+
+    /// Committer time of `commit` in unix seconds.
+    fn commit_time(&self, repo: &Path, commit: &str) -> Result<i64>;
+
+    fn head_commit(&self, repo: &Path) -> Result<String>;
+
+    fn remote_url(&self, repo: &Path) -> Result<String>;
+}
+
+static CLI: CliBackend = CliBackend;
+static GIX: gitoxide::GixBackend = gitoxide::GixBackend;
+
+/// The backend picked by `$DOTHUB_GIT_BACKEND` or the `git_backend` setting.
+pub fn configured() -> BackendKind {
+    static KIND: OnceLock<BackendKind> = OnceLock::new();
+    *KIND.get_or_init(|| {
+        if let Ok(value) = std::env::var(BACKEND_ENV) {
+            match value.parse() {
+                Ok(kind) => return kind,
+                Err(e) => eprintln!("Warning: ${}: {:#}", BACKEND_ENV, e),
+            }
+        }
+        match Config::load_or_default() {
+            Ok(config) => config.settings.git_backend,
+            Err(e) => {
+                eprintln!("Warning: {:#}", e);
+                BackendKind::default()
+            }
+        }
+    })
+}
+
+pub fn current() -> &'static dyn GitBackend {
+    match configured() {
+        BackendKind::Cli => &CLI,
+        BackendKind::Gix => &GIX,
+    }
+}
+
+/// The configured backend, unless the repo was cloned in a way only the git
+/// binary understands.
+pub fn for_strategy(strategy: &CloneStrategy) -> &'static dyn GitBackend {
+    if strategy.blobless || strategy.sparse {
+        &CLI
+    } else {
+        current()
+    }
+}
+
+/// Drives the `git` binary; see [`crate::git`].
+pub struct CliBackend;
+
+impl GitBackend for CliBackend {
+    fn name(&self) -> &'static str {
+        "cli"
+    }
+
+    fn ensure_available(&self) -> Result<()> {
+        git::ensure_available()
+    }
+
+    fn clone_repo(&self, url: &str, dest: &Path, strategy: &CloneStrategy) -> Result<()> {
+        git::ensure_available()?;
+        git::clone(url, dest, strategy)
+    }
+
+    fn fetch(&self, repo: &Path) -> Result<()> {
+        // Shallow and partial clones keep their settings in the repo's git
+        // config, so this fetches only the new commits without deepening history
+        git::run(repo, &["fetch", "--quiet", "--tags", "origin"])
+    }
+
+    fn upstream_commit(&self, repo: &Path) -> Result<String> {
+        git::output(repo, &["rev-parse", "@{upstream}"])
+    }
+
+    fn fast_forward(&self, repo: &Path) -> Result<FastForward> {
+        let from = git::head_commit(repo)?;
+        git::run(repo, &["merge", "--quiet", "--ff-only", "@{upstream}"])?;
+        let to = git::head_commit(repo)?;
+        let changed_files = if from == to {
+            Vec::new()
+        } else {
+            git::output(repo, &["diff", "--name-only", &from, &to])?
+                .lines()
+                .map(str::to_string)
+                .collect()
+        };
+        Ok(FastForward {
+            from,
+            to,
+            changed_files,
+        })
+    }
+
+    fn status(&self, repo: &Path) -> Result<RepoStatus> {
+        let branch = git::output(repo, &["symbolic-ref", "--quiet", "--short", "HEAD"]).ok();
+        let dirty =
+            !git::output(repo, &["status", "--porcelain", "--untracked-files=no"])?.is_empty();
+        Ok(RepoStatus { branch, dirty })
+    }
+
+    fn ahead_behind(&self, repo: &Path) -> Result<(usize, usize)> {
+        let counts = git::output(
+            repo,
+            &["rev-list", "--left-right", "--count", "HEAD...@{upstream}"],
+        )?;
+        let mut parts = counts.split_whitespace().map(str::parse::<usize>);
+        match (parts.next(), parts.next()) {
+            (Some(Ok(ahead)), Some(Ok(behind))) => Ok((ahead, behind)),
+            _ => bail!("Unexpected git rev-list output: {}", counts),
+        }
+    }
+
+    fn commit_time(&self, repo: &Path, commit: &str) -> Result<i64> {
+        let time = git::output(repo, &["log", "-1", "--format=%ct", commit])?;
+        time.parse()
+            .map_err(|_| anyhow::anyhow!("Unexpected commit time: {}", time))
+    }
+
+    fn head_commit(&self, repo: &Path) -> Result<String> {
+        git::head_commit(repo)
+    }
+
+    fn remote_url(&self, repo: &Path) -> Result<String> {
+        git::output(repo, &["remote", "get-url", "origin"])
+    }
+}
@@ -0,0 +1,254 @@
+//! In-process git via gitoxide, for machines without a `git` binary.
+
+use super::{FastForward, GitBackend, RepoStatus};
+use crate::git;
+use crate::meta::CloneStrategy;
+use anyhow::{bail, Context, Result};
+use gix::bstr::{BString, ByteSlice};
+use gix::progress::Discard;
+use gix::refs::transaction::PreviousValue;
+use gix::refs::FullName;
+use gix::remote::Direction;
+use gix::ObjectId;
+use std::collections::BTreeSet;
+use std::fs;
+use std::num::NonZeroU32;
+use std::path::Path;
+use std::sync::atomic::AtomicBool;
+
+pub struct GixBackend;
+
+fn open(repo: &Path) -> Result<gix::Repository> {
+    let mut repo = gix::open(repo).with_context(|| format!("Opening {}", repo.display()))?;
+    // Reflog entries need a committer, which machines that only consume
+    // dotfiles often have not configured
+    repo.committer_or_set_generic_fallback()?;
+    Ok(repo)
+}
+
+impl GitBackend for GixBackend {
+    fn name(&self) -> &'static str {
+        "gix"
+    }
+
+    fn ensure_available(&self) -> Result<()> {
+        Ok(())
+    }
+
+    fn clone_repo(&self, url: &str, dest: &Path, strategy: &CloneStrategy) -> Result<()> {
+        if strategy.blobless || strategy.sparse {
+            bail!("Blobless and sparse clones need the cli git backend");
+        }
+        let interrupt = AtomicBool::new(false);
+        let mut prepare =
+            gix::prepare_clone(url, dest).with_context(|| format!("Cloning {}", url))?;
+        if let Some(depth) = strategy.depth.and_then(NonZeroU32::new) {
+            prepare = prepare.with_shallow(gix::remote::fetch::Shallow::DepthAtRemote(depth));
+        }
+        let (mut checkout, _) = prepare
+            .fetch_then_checkout(Discard, &interrupt)
+            .with_context(|| format!("Cloning {}", url))?;
+        checkout
+            .main_worktree(Discard, &interrupt)
+            .with_context(|| format!("Checking out {}", dest.display()))?;
+        // gitoxide does not check out submodules; that still takes the git binary
+        git::update_submodules(dest)
+    }
+
+    fn fetch(&self, repo: &Path) -> Result<()> {
+        let repo = open(repo)?;
+        let remote = repo
+            .find_default_remote(Direction::Fetch)
+            .context("No remote to fetch from")??;
+        remote
+            .connect(Direction::Fetch)?
+            .prepare_fetch(Discard, Default::default())?
+            .receive(Discard, &AtomicBool::new(false))
+            .context("Fetching from origin")?;
+        Ok(())
+    }
+
+    fn fast_forward(&self, path: &Path) -> Result<FastForward> {
+        let repo = open(path)?;
+        let (branch, upstream, to) = upstream(&repo)?;
+        let from = repo.head_id()?.detach();
+        if from == to {
+            return Ok(FastForward {
+                from: from.to_string(),
+                to: to.to_string(),
+                changed_files: Vec::new(),
+            });
+        }
+        if repo.merge_base(from, to)?.detach() != from {
+            bail!(
+                "Not possible to fast-forward: {} has diverged from {}",
+                branch.shorten(),
+                upstream.shorten()
+            );
+        }
+        if repo.is_dirty()? {
+            bail!("Not fast-forwarding over uncommitted changes");
+        }
+        let workdir = repo
+            .workdir()
+            .context("Repository has no worktree")?
+            .to_path_buf();
+
+        let old = repo.index_from_tree(&repo.find_commit(from)?.tree_id()?)?;
+        let mut new = repo.index_from_tree(&repo.find_commit(to)?.tree_id()?)?;
+        let changed: BTreeSet<BString> = new
+            .entries()
+            .iter()
+            .filter(|e| {
+                old.entry_by_path(e.path(&new))
+                    .is_none_or(|o| o.id != e.id || o.mode != e.mode)
+            })
+            .map(|e| e.path(&new).to_owned())
+            .collect();
+        let removed: BTreeSet<BString> = old
+            .entries()
+            .iter()
+            .map(|e| e.path(&old))
+            .filter(|p| new.entry_by_path(p).is_none())
+            .map(|p| p.to_owned())
+            .collect();
+
+        // Deleted first, so a file that turns into a directory (or back) has
+        // room for its replacement
+        for file in &removed {
+            remove_entry(&workdir, &workdir.join(file.to_path()?))?;
+        }
+        let mut partial = new.clone();
+        partial.remove_entries(|_, path, _| !changed.contains(path));
+        let mut options =
+            repo.checkout_options(gix::worktree::stack::state::attributes::Source::IdMapping)?;
+        options.overwrite_existing = true;
+        let outcome = gix::worktree::state::checkout(
+            &mut partial,
+            &workdir,
+            repo.objects.clone().into_arc()?,
+            &Discard,
+            &Discard,
+            &AtomicBool::new(false),
+            options,
+        )
+        .context("Checking out the new commit")?;
+        if let Some(failed) = outcome.errors.first() {
+            bail!("Checking out {}: {}", failed.path, failed.error);
+        }
+
+        // Stat data lets later status checks skip unchanged files: fresh from
+        // the checkout for written files, kept from the old index for the rest
+        let current = repo.index_or_empty()?;
+        for (entry, path) in new.entries_mut_with_paths() {
+            let known = if changed.contains(path) {
+                partial.entry_by_path(path)
+            } else {
+                current.entry_by_path(path).filter(|e| e.id == entry.id)
+            };
+            if let Some(known) = known {
+                entry.stat = known.stat;
+            }
+        }
+        new.write(Default::default()).context("Writing the index")?;
+        repo.reference(
+            branch,
+            to,
+            PreviousValue::MustExistAndMatch(from.into()),
+            "dothub: fast-forward",
+        )?;
+
+        Ok(FastForward {
+            from: from.to_string(),
+            to: to.to_string(),
+            changed_files: changed.union(&removed).map(|p| p.to_string()).collect(),
+        })
+    }
+
+    fn upstream_commit(&self, path: &Path) -> Result<String> {
+        let repo = open(path)?;
+        Ok(upstream(&repo)?.2.to_string())
+    }
+
+    fn status(&self, path: &Path) -> Result<RepoStatus> {
+        let repo = open(path)?;
+        Ok(RepoStatus {
+            branch: repo.head_name()?.map(|name| name.shorten().to_string()),
+            dirty: repo.is_dirty()?,
+        })
+    }
+
+    fn ahead_behind(&self, path: &Path) -> Result<(usize, usize)> {
+        let repo = open(path)?;
+        let (_, _, upstream) = upstream(&repo)?;
+        let head = repo.head_id()?.detach();
+        Ok((
+            count_commits(&repo, head, upstream)?,
+            count_commits(&repo, upstream, head)?,
+        ))
+    }
+
+    fn commit_time(&self, path: &Path, commit: &str) -> Result<i64> {
+        let repo = open(path)?;
+        let id = ObjectId::from_hex(commit.as_bytes())
+            .with_context(|| format!("Not a full commit id: {}", commit))?;
+        let time = repo.find_commit(id)?.time()?;
+        Ok(time.seconds)
+    }
+
+    fn head_commit(&self, path: &Path) -> Result<String> {
+        Ok(open(path)?.head_id()?.to_string())
+    }
+
+    fn remote_url(&self, path: &Path) -> Result<String> {
+        let repo = open(path)?;
+        let remote = repo.find_remote("origin")?;
+        let url = remote
+            .url(Direction::Fetch)
+            .context("origin has no fetch URL")?;
+        Ok(url.to_bstring().to_string())
+    }
+}
+
+/// The checked out branch, the remote-tracking branch it follows and the
+/// commit that one points at.
+fn upstream(repo: &gix::Repository) -> Result<(FullName, FullName, ObjectId)> {
+    let branch = repo.head_name()?.context("HEAD is detached")?;
+    let upstream = repo
+        .branch_remote_tracking_ref_name(branch.as_ref(), Direction::Fetch)
+        .with_context(|| format!("{} has no upstream branch", branch.shorten()))??
+        .into_owned();
+    let id = repo
+        .find_reference(upstream.as_ref())?
+        .peel_to_id()?
+        .detach();
+    Ok((branch, upstream, id))
+}
+
+/// Commits reachable from `tip` but not from `hidden`.
+fn count_commits(repo: &gix::Repository, tip: ObjectId, hidden: ObjectId) -> Result<usize> {
+    let mut count = 0;
+    for info in repo.rev_walk([tip]).with_hidden([hidden]).all()? {
+        info?;
+        count += 1;
+    }
+    Ok(count)
+}
+
+/// Delete a file dropped from the tree, then the directories it leaves empty.
+fn remove_entry(workdir: &Path, path: &Path) -> Result<()> {
+    match fs::symlink_metadata(path) {
+        // A submodule's checkout; git leaves those in place too
+        Ok(m) if m.is_dir() => return Ok(()),
+        Ok(_) => fs::remove_file(path).with_context(|| format!("Removing {}", path.display()))?,
+        Err(_) => return Ok(()),
+    }
+    let mut dir = path.parent();
+    while let Some(d) = dir.filter(|d| *d != workdir) {
+        if fs::remove_dir(d).is_err() {
+            break;
+        }
+        dir = d.parent();
+    }
+    Ok(())
+}
@@ -0,0 +1,124 @@
+//! `~/.config/dothub/dothub.yml`: the repos and links a machine should have.
+
+use crate::backend::BackendKind;
+use crate::dothub_config_dir;
+use crate::meta::{CloneStrategy, LocalChanges, TrustPolicy};
+use anyhow::{Context, Result};
+use serde::Deserialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+const CONFIG_FILE: &str = "dothub.yml";
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    #[serde(default)]
+    pub repos: Vec<DeclaredRepo>,
+    #[serde(default)]
+    pub links: Vec<DeclaredLink>,
+    #[serde(default)]
+    pub settings: Settings,
+}
+
+/// Machine-wide behaviour, under `settings:` in dothub.yml.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Settings {
+    /// `cli` (the git binary) or `gix` (in-process)
+    #[serde(default)]
+    pub git_backend: BackendKind,
+    /// What update does with local modifications unless a repo says otherwise
+    #[serde(default)]
+    pub on_local_changes: LocalChanges,
+    /// Look for upstream changes in the background at most this often; 0 turns it off
+    #[serde(default = "default_update_check_hours")]
+    pub update_check_hours: u64,
+    /// Delete removed repos from the trash after this many days; 0 keeps them
+    #[serde(default = "default_trash_days")]
+    pub trash_days: u64,
+}
+
+fn default_update_check_hours() -> u64 {
+    24
+}
+
+fn default_trash_days() -> u64 {
+    30
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            git_backend: BackendKind::default(),
+            on_local_changes: LocalChanges::default(),
+            update_check_hours: default_update_check_hours(),
+            trash_days: default_trash_days(),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct DeclaredRepo {
+    pub url: String,
+    /// Store name; derived from the URL when absent
+    #[serde(default)]
+    pub name: Option<String>,
+    /// Branch, tag or commit to pin
+    #[serde(default, rename = "ref")]
+    pub git_ref: Option<String>,
+    #[serde(default, flatten)]
+    pub clone: CloneStrategy,
+    /// `require_signed` and `trusted_keys`
+    #[serde(default, flatten)]
+    pub trust: TrustPolicy,
+    /// Overrides `settings.on_local_changes` for this repo
+    #[serde(default)]
+    pub on_local_changes: Option<LocalChanges>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct DeclaredLink {
+    /// Repo name in the store
+    pub source: String,
+    /// Subdirectory of the repo to link instead of its root
+    #[serde(default)]
+    pub path: Option<String>,
+    /// Directory name under ~/.config
+    pub target: String,
+    #[serde(default)]
+    pub mode: LinkMode,
+}
+
+/// What to do with whatever currently sits at a link target.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LinkMode {
+    /// Move it aside to `<target>.dothub-bak-<time>`
+    #[default]
+    Backup,
+    /// Delete it
+    Replace,
+}
+
+pub fn default_path() -> Result<PathBuf> {
+    Ok(dothub_config_dir()?.join(CONFIG_FILE))
+}
+
+impl Config {
+    pub fn load(path: &Path) -> Result<Self> {
+        let text =
+            fs::read_to_string(path).with_context(|| format!("Reading {}", path.display()))?;
+        serde_yaml::from_str(&text).with_context(|| format!("Parsing {}", path.display()))
+    }
+
+    /// The default config file, or an empty config when there is none.
+    pub fn load_or_default() -> Result<Self> {
+        let path = default_path()?;
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        Self::load(&path)
+    }
+}
@@ -0,0 +1,168 @@
+//! `dothub fork`: keep personal tweaks to an installed config on a local
+//! branch that `dothub update` rebases (or merges) onto upstream.
+
+use crate::meta::{Fork, Integrate, StoreMeta};
+use crate::{dothub_dir, git, resolve_repo};
+use anyhow::{bail, Result};
+use std::path::Path;
+
+/// What bringing upstream into a fork branch did.
+pub enum Integrated {
+    Current,
+    Moved {
+        from: String,
+        to: String,
+        files: usize,
+    },
+    /// Upstream conflicts with local commits; the branch was left untouched
+    Conflict(String),
+}
+
+pub fn cmd_fork(name: &str, branch: &str, integrate: Integrate) -> Result<()> {
+    git::ensure_available()?;
+    let entry = resolve_repo(name)?;
+    let path = &entry.path;
+    let root = dothub_dir();
+    let meta = StoreMeta::load(&root)?;
+    let repo_meta = meta.repo(&entry.key).cloned().unwrap_or_default();
+    if !repo_meta.source.is_git() || !path.join(".git").exists() {
+        bail!("{} is not a git repository", entry.alias);
+    }
+    if let Some(pin) = &repo_meta.pin {
+        bail!(
+            "{} is pinned at {}; run dothub unpin {} first",
+            entry.alias,
+            pin,
+            entry.alias
+        );
+    }
+
+    let fork = Fork {
+        branch: branch.to_string(),
+        integrate,
+    };
+    let upstream = match &repo_meta.fork {
+        Some(current) if current.branch == branch => upstream_of(path, branch)?,
+        _ => {
+            // Whatever tracks upstream right now is where the fork starts from
+            let upstream = match git::output(path, &["rev-parse", "--abbrev-ref", "@{upstream}"]) {
+                Ok(u) => u,
+                Err(_) => format!("origin/{}", git::default_branch(path)?),
+            };
+            let local = format!("refs/heads/{}", branch);
+            if git::run(path, &["rev-parse", "--verify", "--quiet", &local]).is_ok() {
+                git::run(path, &["checkout", "--quiet", branch])?;
+            } else {
+                // Uncommitted tweaks come along onto the new branch
+                git::run(path, &["checkout", "--quiet", "-b", branch])?;
+            }
+            git::run(
+                path,
+                &["branch", "--quiet", "--set-upstream-to", &upstream, branch],
+            )?;
+            upstream
+        }
+    };
+    StoreMeta::update(&root, |meta| {
+        meta.repo_mut(&entry.key).fork = Some(fork.clone())
+    })?;
+
+    println!(
+        "{} now follows local branch {} (tracking {})",
+        entry.alias, branch, upstream
+    );
+    let how = match integrate {
+        Integrate::Rebase => format!("rebase them onto {}", upstream),
+        Integrate::Merge => format!("merge {} into them", upstream),
+    };
+    println!(
+        "Commit your changes in {}; dothub update will {}.",
+        path.display(),
+        how
+    );
+    Ok(())
+}
+
+/// Remote-tracking branch a local branch follows, e.g. `origin/main`.
+fn upstream_of(repo: &Path, branch: &str) -> Result<String> {
+    git::output(
+        repo,
+        &[
+            "rev-parse",
+            "--abbrev-ref",
+            &format!("{}@{{upstream}}", branch),
+        ],
+    )
+}
+
+/// Whether the fork branch already contains everything upstream has.
+pub fn is_current(repo: &Path) -> bool {
+    git::run(
+        repo,
+        &["merge-base", "--is-ancestor", "@{upstream}", "HEAD"],
+    )
+    .is_ok()
+}
+
+/// Rebase or merge the already fetched upstream into the fork branch.
+pub fn integrate(repo: &Path, fork: &Fork, dirty: bool) -> Result<Integrated> {
+    git::ensure_available()?;
+    let checked_out = git::output(repo, &["symbolic-ref", "--quiet", "--short", "HEAD"]).ok();
+    if checked_out.as_deref() != Some(fork.branch.as_str()) {
+        bail!(
+            "Fork branch {} is not checked out; run git checkout {} in {}",
+            fork.branch,
+            fork.branch,
+            repo.display()
+        );
+    }
+    if is_current(repo) {
+        return Ok(Integrated::Current);
+    }
+    let upstream = upstream_of(repo, &fork.branch)?;
+    let verb = fork.integrate.command();
+    if dirty {
+        bail!(
+            "Uncommitted changes on {}; commit or stash them in {} so they can be {}d onto {}",
+            fork.branch,
+            repo.display(),
+            verb,
+            upstream
+        );
+    }
+
+    let from = git::head_commit(repo)?;
+    let result = match fork.integrate {
+        Integrate::Rebase => git::run(repo, &["rebase", "--quiet", &upstream]),
+        Integrate::Merge => git::run(repo, &["merge", "--quiet", "--no-edit", &upstream]),
+    };
+    if let Err(e) = result {
+        let conflicts = git::output(repo, &["diff", "--name-only", "--diff-filter=U"])
+            .unwrap_or_default()
+            .lines()
+            .collect::<Vec<_>>()
+            .join(", ");
+        // Put the branch back so the linked config keeps working
+        let _ = git::run(repo, &[verb, "--abort"]);
+        if conflicts.is_empty() {
+            return Err(e);
+        }
+        return Ok(Integrated::Conflict(format!(
+            "{} of {} onto {} conflicts in {}; resolve it with: cd {} && git {} {}, fix the files, git add them, then git {} --continue",
+            verb,
+            fork.branch,
+            upstream,
+            conflicts,
+            repo.display(),
+            verb,
+            upstream,
+            verb
+        )));
+    }
+
+    let to = git::head_commit(repo)?;
+    let files = git::output(repo, &["diff", "--name-only", &from, &to])?
+        .lines()
+        .count();
+    Ok(Integrated::Moved { from, to, files })
+}
@@ -0,0 +1,276 @@
+//! `dothub gc`: find repos nothing uses any more, move them to the trash and
+//! compact the git repos that stay.
+
+use crate::config::Config;
+use crate::history::{self, History};
+use crate::list::{dir_size, human_size};
+use crate::lock::Lockfile;
+use crate::output;
+use crate::store::{StoreEntry, StoreIndex};
+use crate::sync::resolve_declared;
+use crate::{
+    active_links, cmd_remove, confirm, dothub_config_dir, dothub_dir, ensure_store_dir,
+    entry_remote, git, load_store, progress, styled_table,
+};
+use anyhow::{Context, Result};
+use serde::Serialize;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::io::IsTerminal;
+use std::path::Path;
+use std::time::UNIX_EPOCH;
+
+/// A repo nothing links to or declares.
+#[derive(Serialize)]
+struct Unused {
+    name: String,
+    key: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    remote: Option<String>,
+    size: u64,
+    /// Unix seconds; absent when the filesystem does not say
+    #[serde(skip_serializing_if = "Option::is_none")]
+    last_used: Option<u64>,
+}
+
+/// A git repo `git gc` ran on.
+#[derive(Serialize)]
+struct Compacted {
+    name: String,
+    before: u64,
+    after: u64,
+}
+
+#[derive(Serialize)]
+struct Report {
+    unused: Vec<Unused>,
+    /// Trash entries the unused repos went to
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    trashed: Vec<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    compacted: Vec<Compacted>,
+}
+
+pub fn cmd_gc(dry_run: bool, yes: bool, jobs: usize) -> Result<()> {
+    ensure_store_dir()?;
+    let root = dothub_dir();
+    let (meta, index) = load_store()?;
+    let used = in_use(&index)?;
+
+    let mut unused: Vec<Unused> = index
+        .entries
+        .iter()
+        .filter(|e| !used.contains(&e.key))
+        .map(|e| Unused {
+            name: e.alias.clone(),
+            key: e.key.clone(),
+            remote: entry_remote(&meta, e),
+            size: dir_size(&e.path).unwrap_or(0),
+            last_used: last_used(&root, e),
+        })
+        .collect();
+    // Biggest first; among equals, the one left alone longest
+    unused.sort_by(|a, b| {
+        b.size
+            .cmp(&a.size)
+            .then(a.last_used.unwrap_or(0).cmp(&b.last_used.unwrap_or(0)))
+    });
+    let mut report = Report {
+        unused,
+        trashed: Vec::new(),
+        compacted: Vec::new(),
+    };
+
+    if !output::structured() {
+        print_unused(&report.unused);
+    }
+    if dry_run {
+        return output::emit(&report);
+    }
+
+    if !report.unused.is_empty() {
+        let question = match report.unused.len() {
+            1 => "Move it to the trash?".to_string(),
+            n => format!("Move these {} repos to the trash?", n),
+        };
+        if confirm(&question, yes)? {
+            for repo in &report.unused {
+                report.trashed.push(cmd_remove(&repo.key, true)?.trash);
+            }
+        } else if !output::structured() {
+            println!("Left them in place.");
+        }
+    }
+
+    let gone: BTreeSet<&str> = if report.trashed.is_empty() {
+        BTreeSet::new()
+    } else {
+        report.unused.iter().map(|u| u.key.as_str()).collect()
+    };
+    let remaining: Vec<(String, &StoreEntry)> = index
+        .entries
+        .iter()
+        .filter(|e| !gone.contains(e.key.as_str()) && e.path.join(".git").is_dir())
+        .map(|e| (e.alias.clone(), e))
+        .collect();
+    // Compacting is only offered; without a terminal just --yes takes it up
+    if remaining.is_empty()
+        || git::ensure_available().is_err()
+        || (!yes && !std::io::stdin().is_terminal())
+    {
+        return output::emit(&report);
+    }
+    let question = format!(
+        "Run git gc on {} git repo{} to reclaim space?",
+        remaining.len(),
+        if remaining.len() == 1 { "" } else { "s" }
+    );
+    if confirm(&question, yes)? {
+        let results = progress::run(
+            jobs,
+            &remaining,
+            |entry, status| compact(entry, status),
+            |r| match r {
+                Ok(c) => format!("{} -> {}", human_size(c.before), human_size(c.after)),
+                Err(e) => format!("failed: {:#}", e),
+            },
+        );
+        report.compacted = results.into_iter().filter_map(Result::ok).collect();
+        if !output::structured() {
+            let saved: u64 = report
+                .compacted
+                .iter()
+                .map(|c| c.before.saturating_sub(c.after))
+                .sum();
+            println!("Reclaimed {}", human_size(saved));
+        }
+    }
+    output::emit(&report)
+}
+
+/// Keys of repos that a link in ~/.config points into, or that dothub.yml,
+/// another config or a lockfile in ~/.config/dothub mentions.
+fn in_use(index: &StoreIndex) -> Result<BTreeSet<String>> {
+    let mut used = BTreeSet::new();
+    let links = active_links()?;
+    for entry in &index.entries {
+        let repo = entry
+            .path
+            .canonicalize()
+            .unwrap_or_else(|_| entry.path.clone());
+        if links.iter().any(|(_, target)| target.starts_with(&repo)) {
+            used.insert(entry.key.clone());
+        }
+    }
+
+    let dir = dothub_config_dir()?;
+    let Ok(files) = fs::read_dir(&dir) else {
+        return Ok(used);
+    };
+    for file in files {
+        let path = file?.path();
+        let ext = path.extension().and_then(|e| e.to_str()).unwrap_or("");
+        // A profile that cannot be read could protect anything, so stop here
+        // rather than offer its repos for removal
+        match ext {
+            "yml" | "yaml" => {
+                let config = Config::load(&path)
+                    .with_context(|| format!("Reading profile {}", path.display()))?;
+                declared_by(&config, index, &mut used)?;
+            }
+            "lock" => {
+                let lock = Lockfile::load(&path)
+                    .with_context(|| format!("Reading lockfile {}", path.display()))?;
+                used.extend(lock.repos.into_keys());
+            }
+            _ => {}
+        }
+    }
+    Ok(used)
+}
+
+/// Add the repos `config` declares or links to `used`.
+fn declared_by(config: &Config, index: &StoreIndex, used: &mut BTreeSet<String>) -> Result<()> {
+    let mut aliases = BTreeMap::new();
+    for repo in &config.repos {
+        let (alias, key, _) = resolve_declared(index, repo);
+        used.insert(key.clone());
+        aliases.insert(alias, key);
+    }
+    for link in &config.links {
+        match aliases.get(&link.source) {
+            Some(key) => {
+                used.insert(key.clone());
+            }
+            None => {
+                if let Some(entry) = index.find(&link.source)? {
+                    used.insert(entry.key.clone());
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// When dothub or git last touched the repo: its last update, checkout or
+/// fetch, whichever is newest.
+fn last_used(root: &Path, entry: &StoreEntry) -> Option<u64> {
+    let git_dir = entry.path.join(".git");
+    let touched = [
+        entry.path.clone(),
+        git_dir.join("HEAD"),
+        git_dir.join("FETCH_HEAD"),
+        git_dir.join("index"),
+    ]
+    .into_iter()
+    .filter_map(|p| fs::metadata(p).and_then(|m| m.modified()).ok())
+    .filter_map(|t| t.duration_since(UNIX_EPOCH).ok())
+    .map(|d| d.as_secs());
+    let updated = History::load(root, &entry.key)
+        .ok()
+        .and_then(|h| h.updates.last().map(|u| u.time));
+    touched.chain(updated).max()
+}
+
+fn print_unused(unused: &[Unused]) {
+    if unused.is_empty() {
+        println!("Every stored repo is linked or declared; nothing to collect.");
+        return;
+    }
+    let mut table = styled_table();
+    table.set_header(["Repo", "Size", "Last used", "Remote"]);
+    for repo in unused {
+        let name = if repo.key == repo.name {
+            repo.name.clone()
+        } else {
+            format!("{} ({})", repo.name, repo.key)
+        };
+        table.add_row([
+            name,
+            human_size(repo.size),
+            repo.last_used
+                .map(history::format_time)
+                .unwrap_or_else(|| "?".to_string()),
+            repo.remote.clone().unwrap_or_default(),
+        ]);
+    }
+    println!("{}", table);
+    let total: u64 = unused.iter().map(|u| u.size).sum();
+    println!(
+        "{} repo{} not linked or declared anywhere, {} in total",
+        unused.len(),
+        if unused.len() == 1 { " is" } else { "s are" },
+        human_size(total)
+    );
+}
+
+fn compact(entry: &StoreEntry, status: &progress::Status) -> Result<Compacted> {
+    let before = dir_size(&entry.path.join(".git"))?;
+    status.set("git gc");
+    git::run(&entry.path, &["gc", "--quiet"])?;
+    Ok(Compacted {
+        name: entry.alias.clone(),
+        before,
+        after: dir_size(&entry.path.join(".git"))?,
+    })
+}
@@ -0,0 +1,130 @@
+//! Thin wrappers around the `git` binary. Output is captured so several repos
+//! can be worked on at once; stderr ends up in the error on failure.
+
+use crate::meta::CloneStrategy;
+use crate::output::{ErrorKind, Failure};
+use crate::progress;
+use anyhow::{bail, Context, Result};
+use std::io::IsTerminal;
+use std::path::Path;
+use std::process::{Command, Output};
+
+pub fn ensure_available() -> Result<()> {
+    if which::which("git").is_err() {
+        bail!("git is not installed or not found in PATH");
+    }
+    Ok(())
+}
+
+fn git() -> Command {
+    let mut cmd = Command::new("git");
+    // git asks for credentials on the terminal itself, which only works when
+    // there is one and no other job is drawing on it
+    if progress::concurrent() || !std::io::stderr().is_terminal() {
+        cmd.env("GIT_TERMINAL_PROMPT", "0");
+    }
+    cmd
+}
+
+fn check(out: Output, what: &str) -> Result<String> {
+    if !out.status.success() {
+        let stderr = String::from_utf8_lossy(&out.stderr);
+        let reason = stderr
+            .lines()
+            .rev()
+            .find(|l| !l.trim().is_empty())
+            .unwrap_or("no output")
+            .trim();
+        bail!(Failure::new(
+            ErrorKind::Git,
+            format!("{} failed ({}): {}", what, out.status, reason)
+        ));
+    }
+    Ok(String::from_utf8_lossy(&out.stdout).trim().to_string())
+}
+
+/// Clone `url` into `dest` using the given strategy.
+pub fn clone(url: &str, dest: &Path, strategy: &CloneStrategy) -> Result<()> {
+    let out = git()
+        .args(["clone", "--quiet", "--recurse-submodules"])
+        .args(strategy.clone_args())
+        .arg(url)
+        .arg(dest)
+        .output()
+        .with_context(|| "Failed to spawn git clone")?;
+    check(out, "git clone").map(|_| ())
+}
+
+/// Run `git -C <repo> <args>`, failing on a non-zero exit.
+pub fn run(repo: &Path, args: &[&str]) -> Result<()> {
+    output(repo, args).map(|_| ())
+}
+
+/// Run `git -C <repo> <args>` and return its trimmed stdout.
+pub fn output(repo: &Path, args: &[&str]) -> Result<String> {
+    let out = git()
+        .arg("-C")
+        .arg(repo)
+        .args(args)
+        .output()
+        .with_context(|| format!("Running git {} in {}", args.join(" "), repo.display()))?;
+    check(out, &format!("git {}", args.join(" ")))
+}
+
+/// Like [`output`], but git never stops to ask for credentials or an ssh
+/// passphrase, whatever the terminal. For work the user did not ask for.
+pub fn output_unattended(repo: &Path, args: &[&str]) -> Result<String> {
+    let ssh = match std::env::var("GIT_SSH_COMMAND") {
+        Ok(cmd) if !cmd.trim().is_empty() => format!("{} -o BatchMode=yes", cmd),
+        _ => "ssh -o BatchMode=yes".to_string(),
+    };
+    let out = git()
+        .env("GIT_TERMINAL_PROMPT", "0")
+        .env("GIT_SSH_COMMAND", ssh)
+        .arg("-C")
+        .arg(repo)
+        .args(args)
+        .output()
+        .with_context(|| format!("Running git {} in {}", args.join(" "), repo.display()))?;
+    check(out, &format!("git {}", args.join(" ")))
+}
+
+pub fn head_commit(repo: &Path) -> Result<String> {
+    output(repo, &["rev-parse", "HEAD"])
+}
+
+/// Whether `commit` exists in the repo's object database.
+pub fn has_commit(repo: &Path, commit: &str) -> bool {
+    let spec = format!("{}^{{commit}}", commit);
+    output(repo, &["cat-file", "-e", &spec]).is_ok()
+}
+
+/// Check out the submodules recorded at the current commit, if the repo has any.
+pub fn update_submodules(repo: &Path) -> Result<()> {
+    if !repo.join(".gitmodules").exists() {
+        return Ok(());
+    }
+    run(repo, &["submodule", "update", "--init", "--recursive"])
+}
+
+/// Name of the branch `origin/HEAD` points at, e.g. `main`.
+pub fn default_branch(repo: &Path) -> Result<String> {
+    if let Ok(head) = output(
+        repo,
+        &["symbolic-ref", "--short", "refs/remotes/origin/HEAD"],
+    ) {
+        if let Some(branch) = head.strip_prefix("origin/") {
+            return Ok(branch.to_string());
+        }
+    }
+    for candidate in ["main", "master"] {
+        let remote_ref = format!("refs/remotes/origin/{}", candidate);
+        if output(repo, &["rev-parse", "--verify", "--quiet", &remote_ref]).is_ok() {
+            return Ok(candidate.to_string());
+        }
+    }
+    bail!(
+        "Could not determine the default branch of {}",
+        repo.display()
+    )
+}
@@ -0,0 +1,409 @@
+//! What each `dothub update` changed, kept per repo under
+//! `<store>/.dothub/history/<key>.yml` so `dothub log` can show it again.
+
+use crate::meta::state_dir;
+use crate::meta::StoreMeta;
+use crate::output::{ErrorKind, Failure};
+use crate::{dothub_dir, git, pin_checkout, resolve_repo, short_commit};
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const HISTORY_DIR: &str = "history";
+/// Older updates are dropped past this many per repo
+const MAX_RECORDS: usize = 50;
+
+/// File names a shell reads on startup, so changes run on the next login.
+const SHELL_SOURCED: &[&str] = &[
+    ".bashrc",
+    ".bash_profile",
+    ".bash_login",
+    ".bash_logout",
+    ".profile",
+    ".zshrc",
+    ".zshenv",
+    ".zprofile",
+    ".zlogin",
+    ".zlogout",
+    "bashrc",
+    "zshrc",
+    "zshenv",
+    "zprofile",
+    "profile",
+    "config.fish",
+    ".envrc",
+];
+const SHELL_EXTENSIONS: &[&str] = &["sh", "bash", "zsh", "fish", "ksh"];
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct History {
+    #[serde(default)]
+    pub updates: Vec<UpdateRecord>,
+}
+
+/// One update that moved a repo from `from` to `to`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UpdateRecord {
+    /// Unix seconds
+    pub time: u64,
+    pub from: String,
+    pub to: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub commits: Vec<CommitSummary>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub files: Vec<FileChange>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CommitSummary {
+    pub id: String,
+    pub author: String,
+    pub subject: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FileChange {
+    pub path: String,
+    /// `A`dded, `M`odified, `D`eleted or `T`ype changed
+    pub status: char,
+    /// `None` for binary files
+    pub added: Option<u64>,
+    pub removed: Option<u64>,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub executable: bool,
+    /// Shell startup file or shell script
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub shell_sourced: bool,
+}
+
+impl FileChange {
+    fn is_sensitive(&self) -> bool {
+        self.executable || self.shell_sourced
+    }
+}
+
+pub fn history_path(root: &Path, key: &str) -> PathBuf {
+    state_dir(root)
+        .join(HISTORY_DIR)
+        .join(format!("{}.yml", key))
+}
+
+impl History {
+    pub fn load(root: &Path, key: &str) -> Result<Self> {
+        let path = history_path(root, key);
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let text =
+            fs::read_to_string(&path).with_context(|| format!("Reading {}", path.display()))?;
+        serde_yaml::from_str(&text).with_context(|| format!("Parsing {}", path.display()))
+    }
+
+    pub fn save(&self, root: &Path, key: &str) -> Result<()> {
+        let path = history_path(root, key);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).with_context(|| format!("Creating {}", parent.display()))?;
+        }
+        let text = serde_yaml::to_string(self)?;
+        fs::write(&path, text).with_context(|| format!("Writing {}", path.display()))
+    }
+}
+
+/// Append `record` to the repo's history. Each repo has its own file, so
+/// parallel updates do not contend.
+pub fn record(root: &Path, key: &str, record: &UpdateRecord) -> Result<()> {
+    let mut history = History::load(root, key)?;
+    history.updates.push(record.clone());
+    if history.updates.len() > MAX_RECORDS {
+        let excess = history.updates.len() - MAX_RECORDS;
+        history.updates.drain(..excess);
+    }
+    history.save(root, key)
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+/// Summarize `from..to`. Details need the git binary; without it only the
+/// commit range is kept.
+pub fn changelog(repo: &Path, from: &str, to: &str) -> UpdateRecord {
+    let mut record = UpdateRecord {
+        time: now(),
+        from: from.to_string(),
+        to: to.to_string(),
+        commits: Vec::new(),
+        files: Vec::new(),
+    };
+    if git::ensure_available().is_err() {
+        return record;
+    }
+    let range = format!("{}..{}", from, to);
+    if let Ok(out) = git::output(repo, &["log", "--format=%h%x1f%an%x1f%s", &range]) {
+        record.commits = out
+            .lines()
+            .filter_map(|line| {
+                let mut parts = line.splitn(3, '\x1f');
+                Some(CommitSummary {
+                    id: parts.next()?.to_string(),
+                    author: parts.next()?.to_string(),
+                    subject: parts.next().unwrap_or_default().to_string(),
+                })
+            })
+            .collect();
+    }
+    if let Ok(out) = git::output(repo, &["diff", "--raw", "--no-renames", from, to]) {
+        // `:<old mode> <new mode> <old id> <new id> <status>\t<path>`
+        for line in out.lines() {
+            let Some((info, path)) = line.split_once('\t') else {
+                continue;
+            };
+            let fields: Vec<&str> = info.split_whitespace().collect();
+            let (Some(new_mode), Some(status)) = (fields.get(1), fields.get(4)) else {
+                continue;
+            };
+            record.files.push(FileChange {
+                path: path.to_string(),
+                status: status.chars().next().unwrap_or('M'),
+                added: None,
+                removed: None,
+                executable: *new_mode == "100755",
+                shell_sourced: is_shell_sourced(path),
+            });
+        }
+    }
+    if let Ok(out) = git::output(repo, &["diff", "--numstat", "--no-renames", from, to]) {
+        for line in out.lines() {
+            let mut parts = line.splitn(3, '\t');
+            let (Some(added), Some(removed), Some(path)) =
+                (parts.next(), parts.next(), parts.next())
+            else {
+                continue;
+            };
+            if let Some(file) = record.files.iter_mut().find(|f| f.path == path) {
+                file.added = added.parse().ok();
+                file.removed = removed.parse().ok();
+            }
+        }
+    }
+    record
+}
+
+fn is_shell_sourced(path: &str) -> bool {
+    let name = path.rsplit('/').next().unwrap_or(path);
+    if SHELL_SOURCED.contains(&name) {
+        return true;
+    }
+    match name.rsplit_once('.') {
+        Some((stem, ext)) if !stem.is_empty() => SHELL_EXTENSIONS.contains(&ext),
+        _ => false,
+    }
+}
+
+/// `2024-05-01 13:37 UTC` for unix seconds.
+pub fn format_time(secs: u64) -> String {
+    let days = (secs / 86_400) as i64;
+    let rem = secs % 86_400;
+    // Civil date from days since the epoch (Howard Hinnant's algorithm)
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    format!(
+        "{:04}-{:02}-{:02} {:02}:{:02} UTC",
+        year,
+        month,
+        day,
+        rem / 3600,
+        rem % 3600 / 60
+    )
+}
+
+/// Print a record as a short changelog, flagging files that run code.
+pub fn print(name: &str, record: &UpdateRecord) {
+    println!(
+        "{} {}..{} ({})",
+        name,
+        short_commit(&record.from),
+        short_commit(&record.to),
+        format_time(record.time)
+    );
+    for commit in &record.commits {
+        println!("  {} {} ({})", commit.id, commit.subject, commit.author);
+    }
+    if record.files.is_empty() {
+        return;
+    }
+    let added: u64 = record.files.iter().filter_map(|f| f.added).sum();
+    let removed: u64 = record.files.iter().filter_map(|f| f.removed).sum();
+    println!(
+        "  {} files changed, +{} -{}",
+        record.files.len(),
+        added,
+        removed
+    );
+    for file in &record.files {
+        let lines = match (file.added, file.removed) {
+            (Some(a), Some(r)) => format!("+{} -{}", a, r),
+            _ => "binary".to_string(),
+        };
+        let mut tags = Vec::new();
+        if file.executable {
+            tags.push("executable");
+        }
+        if file.shell_sourced {
+            tags.push("shell code");
+        }
+        let marker = if file.is_sensitive() { "!" } else { " " };
+        if tags.is_empty() {
+            println!("  {} {} {} ({})", marker, file.status, file.path, lines);
+        } else {
+            println!(
+                "  {} {} {} ({}) [{}]",
+                marker,
+                file.status,
+                file.path,
+                lines,
+                tags.join(", ")
+            );
+        }
+    }
+    let sensitive = record.files.iter().filter(|f| f.is_sensitive()).count();
+    if sensitive > 0 {
+        println!(
+            "  ! {} changed files are executable or sourced by a shell; review them before your next login",
+            sensitive
+        );
+    }
+}
+
+pub fn cmd_log(name: &str, count: usize) -> Result<()> {
+    let entry = resolve_repo(name)?;
+    let history = History::load(&dothub_dir(), &entry.key)?;
+    if history.updates.is_empty() {
+        println!("No updates recorded for {} yet.", entry.alias);
+        return Ok(());
+    }
+    let start = history.updates.len().saturating_sub(count.max(1));
+    for (i, record) in history.updates[start..].iter().rev().enumerate() {
+        if i > 0 {
+            println!();
+        }
+        print(&entry.alias, record);
+    }
+    Ok(())
+}
+
+/// Go back `target` updates (a small number) or to a commit, and pin the repo
+/// there so the next `dothub update` does not pull the breakage back in.
+pub fn cmd_rollback(name: &str, target: &str) -> Result<()> {
+    git::ensure_available()?;
+    let entry = resolve_repo(name)?;
+    let path = &entry.path;
+    let root = dothub_dir();
+    let meta = StoreMeta::load(&root)?;
+    if !meta
+        .repo(&entry.key)
+        .map(|m| m.source)
+        .unwrap_or_default()
+        .is_git()
+        || !path.join(".git").exists()
+    {
+        bail!("{} is not a git repository", entry.alias);
+    }
+
+    let history = History::load(&root, &entry.key)?;
+    // Short numbers count updates; anything else (including abbreviated
+    // hashes made only of digits) is a commit
+    let (commit, undone) = match target.parse::<usize>() {
+        Ok(steps) if target.len() < 4 => {
+            if steps == 0 {
+                bail!("Nothing to roll back with 0 steps");
+            }
+            let recorded = history.updates.len();
+            if steps > recorded {
+                bail!(
+                    "{} has {} recorded update{}; see dothub log {} -n {}",
+                    entry.alias,
+                    recorded,
+                    if recorded == 1 { "" } else { "s" },
+                    entry.alias,
+                    recorded.max(1)
+                );
+            }
+            let record = &history.updates[recorded - steps];
+            (record.from.clone(), Some(record))
+        }
+        _ => {
+            let commit = git::output(path, &["rev-parse", &format!("{}^{{commit}}", target)])
+                .with_context(|| {
+                    Failure::new(
+                        ErrorKind::InvalidInput,
+                        format!("{} is not a commit in {}", target, entry.alias),
+                    )
+                })?;
+            (commit, None)
+        }
+    };
+
+    let head = git::head_commit(path)?;
+    pin_checkout(&entry, &commit)?;
+
+    match undone {
+        _ if head == commit => {
+            println!("{} is already at {}", entry.alias, short_commit(&commit))
+        }
+        Some(record) => println!(
+            "Rolled back {} from {} to {}, before the update of {}",
+            entry.alias,
+            short_commit(&head),
+            short_commit(&commit),
+            format_time(record.time)
+        ),
+        None => println!(
+            "Rolled back {} from {} to {}",
+            entry.alias,
+            short_commit(&head),
+            short_commit(&commit)
+        ),
+    }
+    println!(
+        "Pinned at {}; dothub update leaves it there until you run dothub unpin {}",
+        short_commit(&commit),
+        entry.alias
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn formats_the_epoch() {
+        assert_eq!(format_time(0), "1970-01-01 00:00 UTC");
+    }
+
+    #[test]
+    fn formats_a_leap_day() {
+        assert_eq!(format_time(1_709_164_800), "2024-02-29 00:00 UTC");
+        assert_eq!(format_time(1_709_213_820), "2024-02-29 13:37 UTC");
+        assert_eq!(format_time(1_709_251_200), "2024-03-01 00:00 UTC");
+    }
+
+    #[test]
+    fn formats_a_year_boundary() {
+        assert_eq!(format_time(1_735_689_599), "2024-12-31 23:59 UTC");
+        assert_eq!(format_time(1_735_689_600), "2025-01-01 00:00 UTC");
+    }
+}
@@ -0,0 +1,458 @@
+//! `dothub info`: everything dothub knows about one installed repo, from the
+//! store, git, ~/.config, dothub.yml and the hub.
+
+use crate::config::Config;
+use crate::list::{self, Column, Row};
+use crate::meta::RepoMeta;
+use crate::store::StoreEntry;
+use crate::{
+    active_links, fetch_hub_items, git, history, hub_link_matches, load_store, output,
+    rank_hub_items, resolve_repo, short_commit,
+};
+use anyhow::Result;
+use serde::Serialize;
+use std::fs;
+use std::io::IsTerminal;
+use std::path::Path;
+
+/// How many commits and README lines to show
+const RECENT_COMMITS: usize = 5;
+const README_LINES: usize = 12;
+
+#[derive(Serialize)]
+struct Info {
+    #[serde(flatten)]
+    repo: Row,
+    /// Hub types listing the repo, with its stars
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    hub: Vec<HubListing>,
+    /// Store metadata: how it was installed and what update does with it
+    #[serde(skip_serializing_if = "Option::is_none")]
+    metadata: Option<RepoMeta>,
+    /// Whether dothub.yml declares the repo
+    declared: bool,
+    /// Git submodules the repo pulls in
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    dependencies: Vec<Submodule>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    commits: Vec<RecentCommit>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    readme: Option<Readme>,
+}
+
+#[derive(Serialize)]
+struct HubListing {
+    #[serde(rename = "type")]
+    ty: String,
+    stars: u64,
+}
+
+#[derive(Serialize)]
+struct Submodule {
+    path: String,
+    url: String,
+}
+
+#[derive(Serialize)]
+struct RecentCommit {
+    id: String,
+    /// Unix seconds
+    time: i64,
+    author: String,
+    subject: String,
+}
+
+#[derive(Serialize)]
+struct Readme {
+    file: String,
+    excerpt: Vec<String>,
+}
+
+pub fn cmd_info(name: &str, url: Option<&str>, offline: bool) -> Result<()> {
+    let entry = resolve_repo(name)?;
+    let (meta, _) = load_store()?;
+    let links = active_links()?;
+    let repo = list::row(&entry, &meta, &links, Column::ALL);
+    let declared = Config::load_or_default()
+        .map(|config| {
+            config.repos.iter().any(|r| {
+                hub_link_matches(&entry, &r.url) || r.name.as_deref() == Some(entry.alias.as_str())
+            })
+        })
+        .unwrap_or(false);
+
+    let info = Info {
+        hub: if offline {
+            Vec::new()
+        } else {
+            hub_listings(&entry, url)
+        },
+        metadata: meta.repo(&entry.key).cloned(),
+        declared,
+        dependencies: submodules(&entry.path),
+        commits: recent_commits(&entry.path),
+        readme: readme(&entry.path),
+        repo,
+    };
+    if output::structured() {
+        return output::emit(&info);
+    }
+    print(&entry, &info);
+    Ok(())
+}
+
+/// Hub types that list the repo. The hub being unreachable is not an error
+/// here; the section is just left out.
+fn hub_listings(entry: &StoreEntry, url: Option<&str>) -> Vec<HubListing> {
+    let Ok(items) = fetch_hub_items(url, &[]) else {
+        return Vec::new();
+    };
+    let matching: Vec<(String, String)> = items
+        .into_iter()
+        .filter(|(_, link)| hub_link_matches(entry, link))
+        .collect();
+    if matching.is_empty() {
+        return Vec::new();
+    }
+    rank_hub_items(matching)
+        .entries
+        .into_iter()
+        .map(|e| HubListing {
+            ty: e.ty,
+            stars: e.stars,
+        })
+        .collect()
+}
+
+fn submodules(repo: &Path) -> Vec<Submodule> {
+    let gitmodules = repo.join(".gitmodules");
+    if !gitmodules.exists() || git::ensure_available().is_err() {
+        return Vec::new();
+    }
+    let file = gitmodules.display().to_string();
+    let Ok(out) = git::output(
+        repo,
+        &[
+            "config",
+            "--file",
+            &file,
+            "--get-regexp",
+            r"^submodule\..*\.(path|url)$",
+        ],
+    ) else {
+        return Vec::new();
+    };
+    // `submodule.<name>.path <value>` and `submodule.<name>.url <value>`
+    let mut found: Vec<(String, Submodule)> = Vec::new();
+    for line in out.lines() {
+        let Some((key, value)) = line.split_once(' ') else {
+            continue;
+        };
+        let Some((name, field)) = key
+            .strip_prefix("submodule.")
+            .and_then(|k| k.rsplit_once('.'))
+        else {
+            continue;
+        };
+        let index = match found.iter().position(|(n, _)| n == name) {
+            Some(i) => i,
+            None => {
+                found.push((
+                    name.to_string(),
+                    Submodule {
+                        path: String::new(),
+                        url: String::new(),
+                    },
+                ));
+                found.len() - 1
+            }
+        };
+        match field {
+            "path" => found[index].1.path = value.to_string(),
+            _ => found[index].1.url = value.to_string(),
+        }
+    }
+    found.into_iter().map(|(_, s)| s).collect()
+}
+
+fn recent_commits(repo: &Path) -> Vec<RecentCommit> {
+    if !repo.join(".git").exists() || git::ensure_available().is_err() {
+        return Vec::new();
+    }
+    let count = format!("-{}", RECENT_COMMITS);
+    let Ok(out) = git::output(repo, &["log", &count, "--format=%H%x1f%ct%x1f%an%x1f%s"]) else {
+        return Vec::new();
+    };
+    out.lines()
+        .filter_map(|line| {
+            let mut parts = line.splitn(4, '\x1f');
+            Some(RecentCommit {
+                id: parts.next()?.to_string(),
+                time: parts.next()?.parse().ok()?,
+                author: parts.next()?.to_string(),
+                subject: parts.next().unwrap_or_default().to_string(),
+            })
+        })
+        .collect()
+}
+
+fn readme(repo: &Path) -> Option<Readme> {
+    let mut candidates: Vec<_> = fs::read_dir(repo)
+        .ok()?
+        .filter_map(|e| e.ok())
+        .filter(|e| {
+            let name = e.file_name().to_string_lossy().to_lowercase();
+            name == "readme" || name.starts_with("readme.")
+        })
+        .collect();
+    // Prefer markdown when there are several
+    candidates.sort_by_key(|e| {
+        !e.file_name()
+            .to_string_lossy()
+            .to_lowercase()
+            .ends_with(".md")
+    });
+    let file = candidates.into_iter().next()?;
+    let text = fs::read_to_string(file.path()).ok()?;
+    Some(Readme {
+        file: file.file_name().to_string_lossy().into_owned(),
+        excerpt: excerpt(&text),
+    })
+}
+
+/// The first lines of prose of a README, with markdown markup removed.
+fn excerpt(text: &str) -> Vec<String> {
+    let mut lines = Vec::new();
+    let mut in_code = false;
+    for raw in text.lines() {
+        let line = raw.trim();
+        if line.starts_with("```") || line.starts_with("~~~") {
+            in_code = !in_code;
+            continue;
+        }
+        // Badges, images and raw html say little in a terminal
+        if in_code || line.starts_with("![") || line.starts_with("[![") || line.starts_with('<') {
+            continue;
+        }
+        if line.is_empty() {
+            if lines.last().is_some_and(|l: &String| !l.is_empty()) {
+                lines.push(String::new());
+            }
+            continue;
+        }
+        let heading = line.starts_with('#');
+        let mut plain = strip_links(line.trim_start_matches('#').trim());
+        plain = plain.replace("**", "").replace("__", "").replace('`', "");
+        if heading {
+            plain = plain.to_uppercase();
+        }
+        lines.push(plain);
+        if lines.len() >= README_LINES {
+            break;
+        }
+    }
+    while lines.last().is_some_and(|l| l.is_empty()) {
+        lines.pop();
+    }
+    lines
+}
+
+/// `[text](url)` becomes `text`. Brackets that do not open a link are kept.
+fn strip_links(line: &str) -> String {
+    let mut out = String::with_capacity(line.len());
+    let mut rest = line;
+    while let Some(open) = rest.find('[') {
+        out.push_str(&rest[..open]);
+        let after = &rest[open + 1..];
+        // Only the `]` closing this bracket may start the `(url)` part
+        let link = after.find(']').and_then(|close| {
+            let text = &after[..close];
+            let url = after[close + 1..].strip_prefix('(')?;
+            let end = url.find(')')?;
+            (!text.contains('[')).then(|| (text, &url[end + 1..]))
+        });
+        match link {
+            Some((text, tail)) => {
+                out.push_str(text);
+                rest = tail;
+            }
+            None => {
+                out.push('[');
+                rest = after;
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+fn print(entry: &StoreEntry, info: &Info) {
+    let repo = &info.repo;
+    let bold = |s: &str| {
+        if std::io::stdout().is_terminal() {
+            format!("\x1b[1m{}\x1b[0m", s)
+        } else {
+            s.to_string()
+        }
+    };
+    let field = |label: &str, value: &str| println!("  {:<12} {}", format!("{}:", label), value);
+
+    println!("{}", bold(&entry.display_name()));
+    field("Path", &entry.path.display().to_string());
+    if let Some(remote) = &repo.remote {
+        field("Remote", remote);
+    }
+    match (&repo.source, &repo.branch, &repo.commit) {
+        (Some(source), _, _) => field("Source", source),
+        (None, branch, Some(commit)) => {
+            let mut checkout = format!(
+                "{} at {}",
+                branch.as_deref().unwrap_or("(detached)"),
+                short_commit(commit)
+            );
+            if let Some(time) = repo.commit_time {
+                checkout.push_str(&format!(" ({})", history::format_time(time.max(0) as u64)));
+            }
+            if repo.dirty == Some(true) {
+                checkout.push_str(", uncommitted changes");
+            }
+            match (repo.ahead, repo.behind) {
+                (Some(0), Some(0)) => checkout.push_str(", even with upstream"),
+                (Some(ahead), Some(behind)) => {
+                    checkout.push_str(&format!(", {} ahead and {} behind upstream", ahead, behind))
+                }
+                _ => {}
+            }
+            field("Checkout", &checkout);
+        }
+        _ => {}
+    }
+    field(
+        "Pin",
+        repo.pin.as_deref().map(list::short_pin).unwrap_or("none"),
+    );
+    if let Some(size) = repo.size {
+        field("Size", &list::human_size(size));
+    }
+    let links = repo.links.clone().unwrap_or_default();
+    field(
+        "Linked as",
+        &if links.is_empty() {
+            "not linked".to_string()
+        } else {
+            links
+                .iter()
+                .map(|l| format!("~/.config/{}", l))
+                .collect::<Vec<_>>()
+                .join(", ")
+        },
+    );
+    if !info.hub.is_empty() {
+        let hub: Vec<String> = info
+            .hub
+            .iter()
+            .map(|h| format!("{} ({} stars)", h.ty, h.stars))
+            .collect();
+        field("Hub", &hub.join(", "));
+    }
+    field(
+        "Declared",
+        if info.declared {
+            "in dothub.yml"
+        } else {
+            "not in dothub.yml"
+        },
+    );
+    if let Some(meta) = &info.metadata {
+        let settings = describe_metadata(meta);
+        if !settings.is_empty() {
+            field("Settings", &settings.join("; "));
+        }
+    }
+    if !info.dependencies.is_empty() {
+        let deps: Vec<String> = info
+            .dependencies
+            .iter()
+            .map(|s| format!("{} ({})", s.path, s.url))
+            .collect();
+        field("Submodules", &deps.join(", "));
+    }
+
+    if !info.commits.is_empty() {
+        println!();
+        println!("{}", bold("Recent commits"));
+        for c in &info.commits {
+            println!(
+                "  {} {} {} ({})",
+                short_commit(&c.id),
+                &history::format_time(c.time.max(0) as u64)[..10],
+                c.subject,
+                c.author
+            );
+        }
+    }
+    if let Some(readme) = &info.readme {
+        println!();
+        println!("{}", bold(&readme.file));
+        for line in &readme.excerpt {
+            if line.is_empty() {
+                println!();
+            } else {
+                println!("  {}", line);
+            }
+        }
+    }
+}
+
+/// Non-default store metadata in words.
+fn describe_metadata(meta: &RepoMeta) -> Vec<String> {
+    let mut out = Vec::new();
+    if let Some(depth) = meta.clone.depth {
+        out.push(format!("shallow clone (depth {})", depth));
+    }
+    if meta.clone.blobless {
+        out.push("blobless clone".to_string());
+    }
+    if meta.clone.sparse {
+        out.push(format!(
+            "sparse checkout of {}",
+            meta.sparse_paths.join(", ")
+        ));
+    }
+    if meta.trust.enforced() {
+        out.push("requires signed commits".to_string());
+    }
+    if let Some(fork) = &meta.fork {
+        out.push(format!(
+            "forked onto {} ({})",
+            fork.branch,
+            fork.integrate.command()
+        ));
+    }
+    if let Some(strategy) = meta.on_local_changes {
+        out.push(format!("local changes: {}", strategy.name()));
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn strips_links_to_their_text() {
+        assert_eq!(
+            strip_links("see [the docs](https://x.y/z) first"),
+            "see the docs first"
+        );
+        assert_eq!(strip_links("[a](1) and [b](2)"), "a and b");
+    }
+
+    #[test]
+    fn keeps_brackets_that_are_not_links() {
+        assert_eq!(strip_links("[bar] baz [x](y)"), "[bar] baz x");
+        assert_eq!(strip_links("[a [b](c)"), "[a b");
+        assert_eq!(strip_links("[x] (y)"), "[x] (y)");
+        assert_eq!(strip_links("unclosed [x](y"), "unclosed [x](y");
+    }
+}
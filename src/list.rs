@@ -0,0 +1,268 @@
+//! `dothub list`: one table row per stored repo with what git and the
+//! filesystem know about it.
+
+use crate::meta::{SourceKind, StoreMeta};
+use crate::store::StoreEntry;
+use crate::{
+    active_links, backend, dothub_dir, ensure_store_dir, entry_remote, history, load_store, output,
+    short_commit, styled_table,
+};
+use anyhow::Result;
+use clap::ValueEnum;
+use serde::Serialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Column {
+    Name,
+    /// URL or path the repo was installed from
+    Remote,
+    /// Checked out branch, or the pin
+    Branch,
+    Commit,
+    /// Committer date of the checked out commit
+    Date,
+    /// Uncommitted changes to tracked files
+    Dirty,
+    /// Commits ahead of and behind upstream as of the last fetch
+    AheadBehind,
+    Size,
+    /// Names under ~/.config that link into the repo
+    Links,
+}
+
+impl Column {
+    pub const ALL: &'static [Column] = &[
+        Column::Name,
+        Column::Remote,
+        Column::Branch,
+        Column::Commit,
+        Column::Date,
+        Column::Dirty,
+        Column::AheadBehind,
+        Column::Size,
+        Column::Links,
+    ];
+
+    fn header(self) -> &'static str {
+        match self {
+            Column::Name => "Repo",
+            Column::Remote => "Remote",
+            Column::Branch => "Branch/pin",
+            Column::Commit => "Commit",
+            Column::Date => "Last commit",
+            Column::Dirty => "Dirty",
+            Column::AheadBehind => "Ahead/behind",
+            Column::Size => "Size",
+            Column::Links => "Linked as",
+        }
+    }
+}
+
+/// What `dothub list` knows about a repo. Only the requested columns are
+/// filled in; `None` means not asked for or not applicable.
+#[derive(Debug, Default, Serialize)]
+pub struct Row {
+    pub name: String,
+    pub key: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub remote: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub source: Option<&'static str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub branch: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pin: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub commit: Option<String>,
+    /// Unix seconds
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub commit_time: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub dirty: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ahead: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub behind: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub size: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub links: Option<Vec<String>>,
+}
+
+pub fn cmd_list(columns: &[Column]) -> Result<()> {
+    ensure_store_dir()?;
+    let root = dothub_dir();
+    let (meta, index) = load_store()?;
+    if index.entries.is_empty() && !output::structured() {
+        println!("No repositories installed in {}.", root.display());
+        return Ok(());
+    }
+    let columns = if columns.is_empty() {
+        Column::ALL
+    } else {
+        columns
+    };
+    let links = if columns.contains(&Column::Links) {
+        active_links()?
+    } else {
+        Vec::new()
+    };
+    let rows: Vec<Row> = index
+        .entries
+        .iter()
+        .map(|entry| row(entry, &meta, &links, columns))
+        .collect();
+    if output::structured() {
+        return output::emit(&rows);
+    }
+
+    let mut table = styled_table();
+    table.set_header(columns.iter().map(|c| c.header()));
+    for row in &rows {
+        table.add_row(columns.iter().map(|&column| cell(column, row)));
+    }
+    println!("{}", table);
+    Ok(())
+}
+
+pub fn row(
+    entry: &StoreEntry,
+    meta: &StoreMeta,
+    links: &[(String, PathBuf)],
+    columns: &[Column],
+) -> Row {
+    let repo_meta = meta.repo(&entry.key);
+    let path = &entry.path;
+    let source = repo_meta.map(|m| m.source).unwrap_or_default();
+    let is_git = source.is_git() && path.join(".git").exists();
+    let git = match repo_meta {
+        Some(m) => backend::for_strategy(&m.clone),
+        None => backend::current(),
+    };
+    let wants = |column| columns.contains(&column);
+    let mut row = Row {
+        name: entry.alias.clone(),
+        key: entry.key.clone(),
+        pin: repo_meta.and_then(|m| m.pin.clone()),
+        ..Row::default()
+    };
+    if wants(Column::Remote) {
+        row.remote = entry_remote(meta, entry);
+    }
+    if !is_git {
+        row.source = Some(source.name());
+    }
+    if is_git && (wants(Column::Branch) || wants(Column::Dirty)) {
+        // Anything git cannot answer stays empty rather than failing the listing
+        if let Ok(status) = git.status(path) {
+            row.branch = status.branch;
+            row.dirty = Some(status.dirty);
+        }
+    }
+    if is_git && (wants(Column::Commit) || wants(Column::Date)) {
+        if let Ok(commit) = git.head_commit(path) {
+            row.commit_time = git.commit_time(path, &commit).ok();
+            row.commit = Some(commit);
+        }
+    }
+    if is_git && row.pin.is_none() && wants(Column::AheadBehind) {
+        // Fails without an upstream, e.g. on a detached checkout
+        if let Ok((ahead, behind)) = git.ahead_behind(path) {
+            row.ahead = Some(ahead);
+            row.behind = Some(behind);
+        }
+    }
+    // A --symlink repo only has a link in the store; its files live elsewhere
+    if wants(Column::Size) && source != SourceKind::Symlink {
+        row.size = dir_size(path).ok();
+    }
+    if wants(Column::Links) {
+        let repo = path.canonicalize().unwrap_or_else(|_| path.clone());
+        row.links = Some(
+            links
+                .iter()
+                .filter(|(_, target)| target.starts_with(&repo))
+                .map(|(name, _)| name.clone())
+                .collect(),
+        );
+    }
+    row
+}
+
+fn cell(column: Column, row: &Row) -> String {
+    let git_only = |value: Option<String>| match row.source {
+        Some(_) => "-".to_string(),
+        None => value.unwrap_or_else(|| "?".to_string()),
+    };
+    match column {
+        Column::Name if row.key == row.name => row.name.clone(),
+        Column::Name => format!("{} ({})", row.name, row.key),
+        Column::Remote => row.remote.clone().unwrap_or_default(),
+        Column::Branch => match (&row.pin, row.source) {
+            (Some(pin), _) => format!("pin {}", short_pin(pin)),
+            (None, Some(source)) => source.to_string(),
+            (None, None) => match (&row.branch, row.dirty) {
+                (Some(branch), _) => branch.clone(),
+                // Status worked, so HEAD is just not on a branch
+                (None, Some(_)) => "(detached)".to_string(),
+                (None, None) => "?".to_string(),
+            },
+        },
+        Column::Commit => git_only(row.commit.as_deref().map(|c| short_commit(c).to_string())),
+        Column::Date => git_only(
+            row.commit_time
+                .map(|t| history::format_time(t.max(0) as u64)),
+        ),
+        Column::Dirty => git_only(row.dirty.map(|d| if d { "yes" } else { "" }.to_string())),
+        Column::AheadBehind => match (row.ahead, row.behind) {
+            (Some(0), Some(0)) => "even".to_string(),
+            (Some(ahead), Some(behind)) => format!("+{} -{}", ahead, behind),
+            _ => "-".to_string(),
+        },
+        Column::Size => match row.size {
+            Some(size) => human_size(size),
+            None if row.source == Some(SourceKind::Symlink.name()) => "-".to_string(),
+            None => "?".to_string(),
+        },
+        Column::Links => row.links.clone().unwrap_or_default().join(", "),
+    }
+}
+
+/// Rollbacks pin full commit ids; show those like any other commit.
+pub fn short_pin(pin: &str) -> &str {
+    if pin.len() == 40 && pin.chars().all(|c| c.is_ascii_hexdigit()) {
+        short_commit(pin)
+    } else {
+        pin
+    }
+}
+
+/// Bytes on disk under `path`, not following symlinks.
+pub fn dir_size(path: &Path) -> std::io::Result<u64> {
+    let md = fs::symlink_metadata(path)?;
+    if !md.is_dir() {
+        return Ok(md.len());
+    }
+    let mut total = 0;
+    for entry in fs::read_dir(path)? {
+        total += dir_size(&entry?.path())?;
+    }
+    Ok(total)
+}
+
+pub fn human_size(bytes: u64) -> String {
+    const UNITS: &[&str] = &["B", "KiB", "MiB", "GiB", "TiB"];
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{} B", bytes)
+    } else {
+        format!("{:.1} {}", size, UNITS[unit])
+    }
+}
@@ -0,0 +1,123 @@
+//! Local modifications in the way of `dothub update`, and the per-repo
+//! strategy for dealing with them.
+
+use crate::meta::{state_dir, LocalChanges, StoreMeta};
+use crate::{dothub_dir, git, resolve_repo, short_commit};
+use anyhow::{Context, Result};
+use serde::Serialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const BACKUP_DIR: &str = "backups";
+const STASH_MESSAGE: &str = "dothub update autostash";
+
+/// What a strategy did to a repo, shown next to its update result.
+#[derive(Serialize)]
+pub struct Applied {
+    pub strategy: LocalChanges,
+    pub message: String,
+    /// Conflict markers were left in the worktree
+    pub conflicts: bool,
+}
+
+impl Applied {
+    pub fn new(strategy: LocalChanges, message: String) -> Self {
+        Applied {
+            strategy,
+            message,
+            conflicts: false,
+        }
+    }
+}
+
+/// Stash uncommitted changes to tracked files.
+pub fn stash(repo: &Path) -> Result<()> {
+    git::ensure_available().context("Stashing local changes needs git")?;
+    git::run(
+        repo,
+        &["stash", "push", "--quiet", "--message", STASH_MESSAGE],
+    )
+}
+
+/// Put stashed changes back. Conflicting files are returned; the stash is
+/// then kept so nothing is lost.
+pub fn unstash(repo: &Path) -> Result<Vec<String>> {
+    if let Err(e) = git::run(repo, &["stash", "pop", "--quiet"]) {
+        let conflicts: Vec<String> = git::output(repo, &["diff", "--name-only", "--diff-filter=U"])
+            .unwrap_or_default()
+            .lines()
+            .map(str::to_string)
+            .collect();
+        if conflicts.is_empty() {
+            return Err(e.context("Restoring stashed local changes"));
+        }
+        return Ok(conflicts);
+    }
+    Ok(Vec::new())
+}
+
+/// Describe how restoring stashed changes went.
+pub fn unstashed(strategy: LocalChanges, repo: &Path, conflicts: Vec<String>) -> Applied {
+    if conflicts.is_empty() {
+        return Applied::new(strategy, "local changes restored".to_string());
+    }
+    Applied {
+        strategy,
+        message: format!(
+            "restoring local changes conflicts in {}; resolve them in {} (a copy stays in git stash)",
+            conflicts.join(", "),
+            repo.display()
+        ),
+        conflicts: true,
+    }
+}
+
+/// Write everything that differs from where the branch left upstream (local
+/// commits and uncommitted changes) to a patch, then reset to upstream.
+pub fn reset_to_upstream(root: &Path, key: &str, repo: &Path) -> Result<PathBuf> {
+    git::ensure_available().context("Resetting to upstream needs git")?;
+    let head = git::head_commit(repo)?;
+    let base = git::output(repo, &["merge-base", "HEAD", "@{upstream}"])?;
+    let dir = state_dir(root).join(BACKUP_DIR).join(key);
+    fs::create_dir_all(&dir).with_context(|| format!("Creating {}", dir.display()))?;
+    // git runs inside the repo, so a relative store path would land elsewhere
+    let dir = dir.canonicalize()?;
+    let secs = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    let patch = dir.join(format!("{}-{}.patch", secs, short_commit(&head)));
+    let output = format!("--output={}", patch.display());
+    git::run(repo, &["diff", "--binary", &output, &base])?;
+    git::run(repo, &["reset", "--quiet", "--hard", "@{upstream}"])?;
+    Ok(patch)
+}
+
+pub fn cmd_local_changes(name: &str, strategy: Option<LocalChanges>, default: bool) -> Result<()> {
+    let entry = resolve_repo(name)?;
+    let root = dothub_dir();
+    if strategy.is_none() && !default {
+        let meta = StoreMeta::load(&root)?;
+        match meta.repo(&entry.key).and_then(|m| m.on_local_changes) {
+            Some(s) => println!("{} uses {} for local changes", entry.alias, s.name()),
+            None => println!(
+                "{} uses the default for local changes (settings.on_local_changes)",
+                entry.alias
+            ),
+        }
+        return Ok(());
+    }
+    StoreMeta::update(&root, |meta| {
+        meta.repo_mut(&entry.key).on_local_changes = strategy
+    })?;
+    match strategy {
+        Some(s) => println!(
+            "dothub update now uses {} when {} has local changes",
+            s.name(),
+            entry.alias
+        ),
+        None => println!("{} uses the default for local changes again", entry.alias),
+    }
+    Ok(())
+}
@@ -0,0 +1,283 @@
+//! `dothub.lock`: the exact commit of every stored repo, for reproducing a
+//! store on another machine.
+
+use crate::meta::{CloneStrategy, RepoMeta, SourceKind, StoreMeta, TrustPolicy};
+use crate::store::{RepoName, StoreIndex};
+use crate::{
+    backend, dothub_config_dir, dothub_dir, ensure_store_dir, git, load_store, output, progress,
+    short_commit, source, trust,
+};
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+const LOCK_FILE: &str = "dothub.lock";
+const LOCK_VERSION: u32 = 1;
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Lockfile {
+    pub version: u32,
+    #[serde(default)]
+    pub repos: BTreeMap<String, LockedRepo>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LockedRepo {
+    /// Short name for repos keyed by their full identity
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub alias: Option<String>,
+    #[serde(default, skip_serializing_if = "SourceKind::is_git")]
+    pub source: SourceKind,
+    pub url: String,
+    /// Pinned branch, tag or commit, if any
+    #[serde(default, rename = "ref", skip_serializing_if = "Option::is_none")]
+    pub git_ref: Option<String>,
+    /// Checked out commit; absent for non-git sources
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub commit: Option<String>,
+    #[serde(default, skip_serializing_if = "CloneStrategy::is_full")]
+    pub clone: CloneStrategy,
+    /// Signature requirements the recorded commit has to meet
+    #[serde(default, skip_serializing_if = "TrustPolicy::is_open")]
+    pub trust: TrustPolicy,
+}
+
+pub fn default_path() -> Result<PathBuf> {
+    Ok(dothub_config_dir()?.join(LOCK_FILE))
+}
+
+impl Lockfile {
+    pub fn load(path: &Path) -> Result<Self> {
+        let text =
+            fs::read_to_string(path).with_context(|| format!("Reading {}", path.display()))?;
+        let lock: Lockfile =
+            serde_yaml::from_str(&text).with_context(|| format!("Parsing {}", path.display()))?;
+        if lock.version > LOCK_VERSION {
+            bail!(
+                "{} was written by a newer dothub (lock version {})",
+                path.display(),
+                lock.version
+            );
+        }
+        Ok(lock)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)
+                .with_context(|| format!("Failed creating {}", parent.display()))?;
+        }
+        let text = serde_yaml::to_string(self).context("Serializing lockfile")?;
+        fs::write(path, text).with_context(|| format!("Writing {}", path.display()))
+    }
+
+    /// Snapshot every repo in the store.
+    pub fn from_store() -> Result<Self> {
+        let (meta, index) = load_store()?;
+        let mut repos = BTreeMap::new();
+        for entry in index.entries {
+            let (name, path) = (entry.key, entry.path);
+            let repo_meta = meta.repo(&name).cloned().unwrap_or_default();
+            let is_git = repo_meta.source.is_git();
+            if is_git && !path.join(".git").exists() {
+                eprintln!("Skipping {}: not a git repository", name);
+                continue;
+            }
+            let url = match repo_meta.url.clone() {
+                Some(u) => u,
+                None if is_git => backend::current()
+                    .remote_url(&path)
+                    .with_context(|| format!("Finding the remote URL of {}", name))?,
+                None => {
+                    eprintln!("Skipping {}: unknown source", name);
+                    continue;
+                }
+            };
+            let commit = if is_git {
+                Some(backend::current().head_commit(&path)?)
+            } else {
+                None
+            };
+            repos.insert(
+                name,
+                LockedRepo {
+                    alias: repo_meta.alias,
+                    source: repo_meta.source,
+                    url,
+                    git_ref: repo_meta.pin,
+                    commit,
+                    clone: repo_meta.clone,
+                    trust: repo_meta.trust,
+                },
+            );
+        }
+        Ok(Lockfile {
+            version: LOCK_VERSION,
+            repos,
+        })
+    }
+}
+
+/// Write the lockfile for the current store and report where it went.
+pub fn write(path: Option<&Path>) -> Result<PathBuf> {
+    ensure_store_dir()?;
+    let path = match path {
+        Some(p) => p.to_path_buf(),
+        None => default_path()?,
+    };
+    let lock = Lockfile::from_store()?;
+    lock.save(&path)?;
+    if !output::structured() {
+        println!(
+            "Locked {} repositories in {}",
+            lock.repos.len(),
+            path.display()
+        );
+    }
+    Ok(path)
+}
+
+pub fn cmd_lock(path: Option<&Path>) -> Result<()> {
+    write(path).map(|_| ())
+}
+
+/// Recreate the store from a lockfile: clone what is missing and move every
+/// git repo to its recorded commit.
+pub fn cmd_sync_locked(path: Option<&Path>, jobs: usize) -> Result<()> {
+    ensure_store_dir()?;
+    let path = match path {
+        Some(p) => p.to_path_buf(),
+        None => default_path()?,
+    };
+    let lock = Lockfile::load(&path)?;
+    let root = dothub_dir();
+
+    let items: Vec<(String, (&String, &LockedRepo))> = lock
+        .repos
+        .iter()
+        .map(|(name, locked)| (name.clone(), (name, locked)))
+        .collect();
+    let results = progress::run(
+        jobs,
+        &items,
+        |(name, locked), status| sync_repo(&root, name, locked, status),
+        |r| match r {
+            Ok(msg) => msg.clone(),
+            Err(e) => format!("failed: {:#}", e),
+        },
+    );
+
+    let mut failed = 0usize;
+    let meta = StoreMeta::update(&root, |meta| {
+        for ((name, (_, locked)), result) in items.iter().zip(&results) {
+            if result.is_err() {
+                failed += 1;
+                continue;
+            }
+            *meta.repo_mut(name) = RepoMeta {
+                alias: locked.alias.clone(),
+                source: locked.source,
+                url: Some(locked.url.clone()),
+                pin: locked.git_ref.clone(),
+                clone: locked.clone.clone(),
+                trust: locked.trust.clone(),
+                ..meta.repo(name).cloned().unwrap_or_default()
+            };
+        }
+        StoreIndex::load(&root, meta)
+    })??;
+
+    for entry in meta.entries {
+        if !lock.repos.contains_key(&entry.key) {
+            println!(
+                "{} is not in {}; leaving it alone",
+                entry.display_name(),
+                path.display()
+            );
+        }
+    }
+
+    if failed > 0 {
+        bail!(
+            "{} of {} repositories failed to sync",
+            failed,
+            lock.repos.len()
+        );
+    }
+    println!(
+        "Synced {} repositories from {}",
+        lock.repos.len(),
+        path.display()
+    );
+    Ok(())
+}
+
+/// Bring one repo to its locked state and describe what happened.
+fn sync_repo(
+    root: &Path,
+    name: &str,
+    locked: &LockedRepo,
+    status: &progress::Status,
+) -> Result<String> {
+    let dest = root.join(RepoName::key(name)?);
+    if let Some(parent) = dest.parent() {
+        fs::create_dir_all(parent)
+            .with_context(|| format!("Failed creating {}", parent.display()))?;
+    }
+    if !locked.source.is_git() {
+        if dest.exists() {
+            return Ok("already present".to_string());
+        }
+        status.set(&format!("installing from {}", locked.url));
+        source::install(locked.source, &locked.url, root, &dest)?;
+        return Ok(format!("installed from {}", locked.url));
+    }
+
+    git::ensure_available()?;
+    let commit = locked
+        .commit
+        .as_deref()
+        .with_context(|| format!("{} has no commit recorded", name))?;
+    let cloned = !dest.exists();
+    if cloned {
+        status.set("cloning");
+        git::clone(&locked.url, &dest, &locked.clone)?;
+    }
+    let current = git::head_commit(&dest)? == commit;
+    if !current && !git::has_commit(&dest, commit) {
+        status.set("fetching");
+        git::run(&dest, &["fetch", "--quiet", "--tags", "origin"])?;
+        if !git::has_commit(&dest, commit) {
+            git::run(&dest, &["fetch", "--quiet", "origin", commit])?;
+        }
+    }
+    if locked.trust.enforced() {
+        status.set("verifying signature");
+        if let Err(e) = trust::verify(&dest, commit, &locked.trust) {
+            // A clone made just now would leave the refused commit checked out
+            if cloned {
+                let _ = fs::remove_dir_all(&dest);
+            }
+            return Err(e);
+        }
+    }
+    if current {
+        return Ok(format!("already at {}", short_commit(commit)));
+    }
+
+    status.set(&format!("checking out {}", short_commit(commit)));
+    if locked.git_ref.is_some() {
+        // Pinned repos never pull, so a detached checkout is fine
+        git::run(&dest, &["checkout", "--quiet", commit])?;
+    } else {
+        // Keep the default branch checked out so later updates can fast-forward;
+        // --keep refuses to clobber local edits
+        let branch = git::default_branch(&dest)?;
+        git::run(&dest, &["checkout", "--quiet", &branch])?;
+        git::run(&dest, &["reset", "--quiet", "--keep", commit])?;
+    }
+    git::update_submodules(&dest)?;
+    Ok(format!("at {}", short_commit(commit)))
+}
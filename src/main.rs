@@ -1,21 +1,51 @@
 use anyhow::{bail, Context, Result};
 use clap::{Args, Parser, Subcommand, ValueEnum};
 use clap_complete::{generate, shells};
+use comfy_table::{modifiers::UTF8_ROUND_CORNERS, presets::UTF8_BORDERS_ONLY, Table};
 use std::collections::HashMap;
-use comfy_table::{Table, presets::UTF8_BORDERS_ONLY, modifiers::UTF8_ROUND_CORNERS};
 use std::env;
-use std::sync::{Arc, atomic::{AtomicBool, Ordering}};
-use std::thread;
-use std::time::Duration;
 use std::fs;
 use std::io;
-use std::io::Write;
+use std::io::{IsTerminal, Write};
 use std::path::{Path, PathBuf};
-use std::process::Command;
+use std::process::ExitCode;
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc,
+};
+use std::thread;
+use std::time::Duration;
+
+mod backend;
+mod config;
+mod fork;
+mod gc;
+mod git;
+mod history;
+mod info;
+mod list;
+mod local;
+mod lock;
+mod meta;
+mod notice;
+mod outdated;
+mod output;
+mod progress;
+mod schedule;
+mod source;
+mod store;
+mod sync;
+mod trash;
+mod trust;
+
+use meta::{
+    CloneStrategy, Fork, Integrate, LocalChanges, RepoMeta, SourceKind, StoreMeta, TrustPolicy,
+};
+use output::{ErrorKind, Failure};
+use store::{RepoName, StoreEntry, StoreIndex};
 
 const DEFAULT_DOTHUB_PATH: &str = ".local/share/dothub";
-const DEFAULT_HUB_URL: &str =
-    "https://raw.githubusercontent.com/huncholane/dothub/main/hub.yml";
+const DEFAULT_HUB_URL: &str = "https://raw.githubusercontent.com/huncholane/dothub/main/hub.yml";
 const GH_TOKEN_HELP_URL: &str = "https://github.com/settings/personal-access-tokens";
 const ASCII_BANNER: &str = include_str!("../ascii.txt");
 
@@ -24,15 +54,18 @@ const ASCII_BANNER: &str = include_str!("../ascii.txt");
     name = "dothub",
     about = "Manage dotfile repos and links",
     version,
-    after_help = "Environment variables:\n  DOTHUB_DIR     Override the store directory (default: XDG data dir, e.g. ~/.local/share/dothub)\n  GITHUB_TOKEN   GitHub token to speed up star fetching via GraphQL (optional)"
+    after_help = "Environment variables:\n  DOTHUB_DIR     Override the store directory (default: XDG data dir, e.g. ~/.local/share/dothub)\n  GITHUB_TOKEN   GitHub token to speed up star fetching via GraphQL (optional)\n  DOTHUB_NO_UPDATE_CHECK  Skip the background check for upstream changes"
 )]
 struct Cli {
     /// Optional filter: types to include (e.g. nvim, tmux). Comma-separated or space-separated.
     #[arg(value_name = "TYPE", num_args = 0.., value_delimiter = ',')]
     types: Vec<String>,
     /// Optional override URL to YAML (defaults to https://github.com/hub.yml)
-    #[arg(long)]
+    #[arg(long, global = true)]
     url: Option<String>,
+    /// Print a JSON or YAML document instead of text (list, info, active, update, install, link, remove, trash, gc and the hub view)
+    #[arg(long, global = true, value_enum, default_value_t)]
+    format: output::Format,
 
     #[command(subcommand)]
     command: Option<Commands>,
@@ -40,18 +73,50 @@ struct Cli {
 
 #[derive(Subcommand)]
 enum Commands {
-    /// Clone a git repository into the dothub store
+    /// Clone a git repository (or copy a local directory or archive) into the dothub store
     Install(InstallArgs),
+    /// Install a hub entry by type and rank (e.g. `get nvim 1` or `get nvim/hygo-nvim`)
+    Get(GetArgs),
     /// Replace ~/.config/<target> with a symlink to a stored repo
     Link(LinkArgs),
-    /// Pull latest changes for all stored repos
-    Update,
+    /// Pull latest changes for stored repos (all of them unless names are given)
+    Update(UpdateArgs),
+    /// Fetch stored repos and show which have upstream changes, without pulling
+    Outdated(OutdatedArgs),
     /// List active links in ~/.config that point into dothub
     Active,
     /// List repositories installed in the dothub store
-    List,
-    /// Remove a repository from the dothub store
+    List(ListArgs),
+    /// Show everything about one stored repo: remote, hub listing, links, pin, commits and README
+    Info(InfoArgs),
+    /// Move a repository from the dothub store to its trash
     Remove(RemoveArgs),
+    /// List, restore or delete removed repos
+    #[command(subcommand)]
+    Trash(TrashCmd),
+    /// Move repos that nothing links to or declares to the trash, then offer git gc
+    Gc(GcArgs),
+    /// Lock a stored repo to a branch, tag or commit
+    Pin(PinArgs),
+    /// Return a pinned repo to tracking its default branch
+    Unpin(UnpinArgs),
+    /// Show or set which commit signatures a stored repo accepts
+    Trust(TrustCmdArgs),
+    /// Keep personal changes to a stored repo on a local branch that update rebases
+    Fork(ForkArgs),
+    /// Show or set what update does when a repo has local changes
+    LocalChanges(LocalChangesArgs),
+    /// Show what the last update of a repo changed
+    Log(LogArgs),
+    /// Put a repo back on the commit it had before an update, pinned there
+    Rollback(RollbackArgs),
+    /// Run dothub update on a timer (systemd user units, or a crontab line)
+    #[command(subcommand)]
+    Schedule(ScheduleCmd),
+    /// Record the exact commit of every stored repo in dothub.lock
+    Lock(LockArgs),
+    /// Bring the store and links in line with dothub.yml (or dothub.lock)
+    Sync(SyncArgs),
     /// Generate shell completions to stdout (bash|zsh|fish|powershell|elvish)
     Completions { shell: Shell },
 }
@@ -61,26 +126,300 @@ enum Commands {
 #[derive(Args)]
 struct InstallArgs {
     /// Git repository URL, e.g. https://github.com/hygo-nvim
+    /// Append @<ref> to pin a branch, tag or commit: https://github.com/foo/bar@v1.0
+    /// Local directories and .tar.gz/.zip archives (paths or URLs) work too
     repo: String,
-    /// Optional explicit name for the repo in the store
+    /// Optional short name (alias) for the repo in the store
     /// Example: dothub install https://github.com/foo/bar my-bar
-    name: Option<String>,
+    #[arg(value_parser = RepoName::alias)]
+    name: Option<RepoName>,
+    /// Symlink a local directory into the store instead of copying it
+    #[arg(long)]
+    symlink: bool,
+    #[command(flatten)]
+    strategy: StrategyArgs,
+    #[command(flatten)]
+    trust: TrustArgs,
+}
+
+#[derive(Args, Default)]
+struct StrategyArgs {
+    /// Shallow clone keeping only this many commits
+    #[arg(long, value_name = "N")]
+    depth: Option<u32>,
+    /// Partial clone that downloads file contents on demand
+    #[arg(long)]
+    blobless: bool,
+    /// Sparse checkout of only the subpaths you link
+    #[arg(long)]
+    sparse: bool,
+}
+
+impl From<StrategyArgs> for CloneStrategy {
+    fn from(args: StrategyArgs) -> Self {
+        CloneStrategy {
+            depth: args.depth,
+            blobless: args.blobless,
+            sparse: args.sparse,
+        }
+    }
+}
+
+#[derive(Args, Default)]
+struct TrustArgs {
+    /// Refuse commits without a good signature from a key gpg or ssh trusts
+    #[arg(long)]
+    require_signed: bool,
+    /// Only accept commits signed by this key id or fingerprint (repeatable)
+    #[arg(long = "trusted-key", value_name = "KEY")]
+    trusted_keys: Vec<String>,
+}
+
+impl From<TrustArgs> for TrustPolicy {
+    fn from(args: TrustArgs) -> Self {
+        TrustPolicy {
+            require_signed: args.require_signed,
+            trusted_keys: args.trusted_keys,
+        }
+    }
+}
+
+#[derive(Args)]
+struct GetArgs {
+    /// Hub type, optionally followed by the repo name (e.g. nvim or nvim/hygo-nvim)
+    entry: String,
+    /// Rank shown by `dothub <type>` or the repo name within the type
+    selector: Option<String>,
+    /// Also link the repo to ~/.config/<type>, backing up what is there
+    #[arg(long)]
+    link: bool,
 }
 
 #[derive(Args)]
 struct LinkArgs {
-    /// Repository name stored under dothub (e.g. hygo-nvim)
-    name: String,
+    /// Repository alias or full name stored under dothub (e.g. hygo-nvim)
+    name: RepoName,
     /// Target directory name under ~/.config (e.g. nvim, alacritty, fish)
     target: String,
+    /// Link a subdirectory of the repo instead of its root
+    #[arg(long, value_name = "SUBPATH")]
+    path: Option<String>,
+    /// Move an existing target aside instead of deleting it
+    #[arg(long)]
+    backup: bool,
+}
+
+#[derive(Args)]
+struct UpdateArgs {
+    /// Repos to update by name or glob (e.g. nvim 'github.com/alice/*'); all when omitted
+    names: Vec<String>,
+    /// Only update repos that something in ~/.config links to
+    #[arg(long)]
+    linked_only: bool,
+    /// Rewrite dothub.lock after updating
+    #[arg(long)]
+    lock: bool,
+    /// What to do with repos that have local changes, for this run only
+    #[arg(long, value_enum, value_name = "STRATEGY")]
+    on_local_changes: Option<LocalChanges>,
+    /// How many repos to update at once
+    #[arg(short, long, value_name = "N", default_value_t = progress::default_jobs())]
+    jobs: usize,
+}
+
+#[derive(Args)]
+struct OutdatedArgs {
+    /// Repos to check by name or glob; all when omitted
+    names: Vec<String>,
+    /// How many repos to fetch at once
+    #[arg(short, long, value_name = "N", default_value_t = progress::default_jobs())]
+    jobs: usize,
 }
 
 #[derive(Args)]
 struct RemoveArgs {
     /// Repository name stored under dothub to remove
+    name: RepoName,
+    /// Do not ask for confirmation
+    #[arg(short, long)]
+    yes: bool,
+}
+
+#[derive(Args)]
+struct GcArgs {
+    /// Only list the unused repos
+    #[arg(long)]
+    dry_run: bool,
+    /// Do not ask before trashing unused repos and compacting the rest
+    #[arg(short, long)]
+    yes: bool,
+    /// How many repos to compact at once
+    #[arg(short, long, value_name = "N", default_value_t = progress::default_jobs())]
+    jobs: usize,
+}
+
+#[derive(Subcommand)]
+enum TrashCmd {
+    /// Show removed repos, newest first, and when they expire
+    List,
+    /// Put a removed repo back in the store
+    Restore(TrashRestoreArgs),
+    /// Permanently delete everything in the trash
+    Empty(TrashEmptyArgs),
+}
+
+#[derive(Args)]
+struct TrashRestoreArgs {
+    /// Entry shown by dothub trash list, or a repo name for its newest entry
     name: String,
 }
 
+#[derive(Args)]
+struct TrashEmptyArgs {
+    /// Do not ask for confirmation
+    #[arg(short, long)]
+    yes: bool,
+}
+
+#[derive(Args)]
+struct PinArgs {
+    /// Repository name stored under dothub
+    name: RepoName,
+    /// Branch, tag or commit to check out and stay on
+    git_ref: String,
+}
+
+#[derive(Args)]
+struct UnpinArgs {
+    /// Repository name stored under dothub
+    name: RepoName,
+}
+
+#[derive(Args)]
+struct TrustCmdArgs {
+    /// Repository name stored under dothub
+    name: RepoName,
+    #[command(flatten)]
+    policy: TrustArgs,
+    /// Accept unsigned commits again
+    #[arg(long, conflicts_with_all = ["require_signed", "trusted_keys"])]
+    off: bool,
+}
+
+#[derive(Args)]
+struct ForkArgs {
+    /// Repository name stored under dothub
+    name: RepoName,
+    /// Local branch to commit your changes on
+    #[arg(long, default_value = "dothub-local")]
+    branch: String,
+    /// Merge upstream into the branch instead of rebasing it
+    #[arg(long)]
+    merge: bool,
+}
+
+#[derive(Args)]
+struct ListArgs {
+    /// Columns to show, comma-separated (default: all)
+    #[arg(long, value_enum, value_delimiter = ',')]
+    columns: Vec<list::Column>,
+}
+
+#[derive(Args)]
+struct InfoArgs {
+    /// Repository name stored under dothub
+    name: RepoName,
+    /// Skip looking the repo up on the hub
+    #[arg(long)]
+    offline: bool,
+}
+
+#[derive(Args)]
+struct LocalChangesArgs {
+    /// Repository name stored under dothub
+    name: RepoName,
+    /// Strategy to use from now on; shows the current one when omitted
+    #[arg(value_enum)]
+    strategy: Option<LocalChanges>,
+    /// Go back to settings.on_local_changes from dothub.yml
+    #[arg(long, conflicts_with = "strategy")]
+    default: bool,
+}
+
+#[derive(Args)]
+struct LogArgs {
+    /// Repository name stored under dothub
+    name: RepoName,
+    /// How many past updates to show, newest first
+    #[arg(short = 'n', long, default_value_t = 1)]
+    count: usize,
+}
+
+#[derive(Args)]
+struct RollbackArgs {
+    /// Repository name stored under dothub
+    name: RepoName,
+    /// How many updates to undo, or a commit to go back to
+    #[arg(default_value = "1")]
+    target: String,
+}
+
+#[derive(Subcommand)]
+enum ScheduleCmd {
+    /// Install and start a timer that runs dothub update
+    Enable(ScheduleArgs),
+    /// Stop the timer and remove its units
+    Disable,
+    /// Show whether the timer is active, when it runs next and its last output
+    Status,
+}
+
+#[derive(Args)]
+struct ScheduleArgs {
+    /// Run every hour
+    #[arg(long, group = "every")]
+    hourly: bool,
+    /// Run every day (the default)
+    #[arg(long, group = "every")]
+    daily: bool,
+    /// Run every week
+    #[arg(long, group = "every")]
+    weekly: bool,
+    /// What scheduled updates do with repos that have local changes
+    #[arg(long, value_enum, value_name = "STRATEGY")]
+    on_local_changes: Option<LocalChanges>,
+    /// Print a crontab line instead of installing systemd units
+    #[arg(long)]
+    cron: bool,
+}
+
+#[derive(Args)]
+struct LockArgs {
+    /// Lockfile to write (default: ~/.config/dothub/dothub.lock)
+    #[arg(long, value_name = "PATH")]
+    file: Option<PathBuf>,
+}
+
+#[derive(Args)]
+struct SyncArgs {
+    /// Clone missing repos and check out the commits recorded in dothub.lock
+    #[arg(long)]
+    locked: bool,
+    /// Config or lockfile to read (default: ~/.config/dothub/dothub.yml or dothub.lock)
+    #[arg(long, value_name = "PATH")]
+    file: Option<PathBuf>,
+    /// Remove installed repos that dothub.yml does not declare
+    #[arg(long, conflicts_with = "locked")]
+    prune: bool,
+    /// Print the plan without changing anything
+    #[arg(long, conflicts_with = "locked")]
+    dry_run: bool,
+    /// How many repos to install at once
+    #[arg(short, long, value_name = "N", default_value_t = progress::default_jobs())]
+    jobs: usize,
+}
+
+#[allow(clippy::enum_variant_names)]
 #[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, ValueEnum)]
 enum Shell {
     Bash,
@@ -90,18 +429,80 @@ enum Shell {
     Elvish,
 }
 
-fn main() -> Result<()> {
+fn main() -> ExitCode {
     let cli = Cli::parse();
+    output::set(cli.format);
+    // These either look at the remotes themselves or produce output for scripts
+    let pending = match cli.command {
+        Some(Commands::Update(_) | Commands::Outdated(_) | Commands::Completions { .. }) => None,
+        _ => notice::start(),
+    };
 
-    match cli.command {
-        Some(Commands::Install(args)) => cmd_install(&args.repo, args.name.as_deref()),
-        Some(Commands::Link(args)) => cmd_link(&args.name, &args.target),
-        Some(Commands::Update) => cmd_update(),
+    let result = match cli.command {
+        Some(Commands::Install(args)) => cmd_install(
+            &args.repo,
+            args.name.as_deref(),
+            args.strategy.into(),
+            args.trust.into(),
+            args.symlink,
+        )
+        .and_then(|installed| output::emit(&installed)),
+        Some(Commands::Get(args)) => {
+            cmd_get(&args.entry, args.selector.as_deref(), args.link, cli.url)
+        }
+        Some(Commands::Link(args)) => {
+            cmd_link(&args.name, args.path.as_deref(), &args.target, args.backup)
+                .and_then(|linked| output::emit(&linked))
+        }
+        Some(Commands::Update(args)) => cmd_update(
+            &args.names,
+            args.linked_only,
+            args.on_local_changes,
+            args.lock,
+            args.jobs,
+        ),
+        Some(Commands::Outdated(args)) => outdated::cmd_outdated(&args.names, args.jobs),
         Some(Commands::Active) => cmd_active(),
-        Some(Commands::List) => cmd_list(),
-        Some(Commands::Remove(args)) => cmd_remove(&args.name),
+        Some(Commands::List(args)) => list::cmd_list(&args.columns),
+        Some(Commands::Info(args)) => info::cmd_info(&args.name, cli.url.as_deref(), args.offline),
+        Some(Commands::Remove(args)) => {
+            cmd_remove(&args.name, args.yes).and_then(|removed| output::emit(&removed))
+        }
+        Some(Commands::Trash(cmd)) => match cmd {
+            TrashCmd::List => trash::cmd_list(),
+            TrashCmd::Restore(args) => trash::cmd_restore(&args.name),
+            TrashCmd::Empty(args) => trash::cmd_empty(args.yes),
+        },
+        Some(Commands::Gc(args)) => gc::cmd_gc(args.dry_run, args.yes, args.jobs),
+        Some(Commands::Pin(args)) => cmd_pin(&args.name, &args.git_ref),
+        Some(Commands::Unpin(args)) => cmd_unpin(&args.name),
+        Some(Commands::Fork(args)) => fork::cmd_fork(
+            &args.name,
+            &args.branch,
+            if args.merge {
+                Integrate::Merge
+            } else {
+                Integrate::Rebase
+            },
+        ),
+        Some(Commands::LocalChanges(args)) => {
+            local::cmd_local_changes(&args.name, args.strategy, args.default)
+        }
+        Some(Commands::Trust(args)) => trust::cmd_trust(&args.name, args.policy.into(), args.off),
+        Some(Commands::Log(args)) => history::cmd_log(&args.name, args.count),
+        Some(Commands::Rollback(args)) => history::cmd_rollback(&args.name, &args.target),
+        Some(Commands::Schedule(cmd)) => cmd_schedule(cmd),
+        Some(Commands::Lock(args)) => lock::cmd_lock(args.file.as_deref()),
+        Some(Commands::Sync(args)) => cmd_sync(args),
         Some(Commands::Completions { shell }) => cmd_completions(shell),
         None => cmd_hub(cli.types, cli.url),
+    };
+    if let Some(pending) = pending {
+        pending.finish();
+    }
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => output::report(&e),
     }
 }
 
@@ -120,6 +521,12 @@ fn dothub_dir() -> PathBuf {
     PathBuf::from(".dothub")
 }
 
+/// Where dothub's own config files live: ~/.config/dothub
+fn dothub_config_dir() -> Result<PathBuf> {
+    let home = dirs::home_dir().context("Unable to determine home directory")?;
+    Ok(home.join(".config").join("dothub"))
+}
+
 fn ensure_store_dir() -> Result<()> {
     let path = dothub_dir();
     if !path.exists() {
@@ -129,12 +536,104 @@ fn ensure_store_dir() -> Result<()> {
 }
 
 fn derive_repo_name(repo_url: &str) -> String {
-    let trimmed = repo_url.trim_end_matches('/').trim_end_matches(".git");
+    let mut trimmed = repo_url.trim_end_matches('/').trim_end_matches(".git");
+    if let Some(ext) = source::archive_extension(trimmed) {
+        trimmed = &trimmed[..trimmed.len() - ext.len()];
+    }
     trimmed.rsplit('/').next().unwrap_or(trimmed).to_string()
 }
 
-fn cmd_install(repo: &str, name_override: Option<&str>) -> Result<()> {
+/// Split `url@ref` into its parts, leaving `git@host:owner/repo` style URLs intact.
+fn split_repo_ref(spec: &str) -> (&str, Option<&str>) {
+    if let Some((url, git_ref)) = spec.rsplit_once('@') {
+        let path = url.split_once("://").map(|(_, rest)| rest).unwrap_or(url);
+        if !git_ref.is_empty() && !git_ref.contains(':') && path.contains('/') {
+            return (url, Some(git_ref));
+        }
+    }
+    (spec, None)
+}
+
+fn load_store() -> Result<(StoreMeta, StoreIndex)> {
+    let root = dothub_dir();
+    let meta = StoreMeta::load(&root)?;
+    let index = StoreIndex::load(&root, &meta)?;
+    Ok((meta, index))
+}
+
+/// Find a stored repo by alias or full name.
+fn resolve_repo(name: &str) -> Result<StoreEntry> {
+    ensure_store_dir()?;
+    let (_, index) = load_store()?;
+    index.resolve(name).cloned()
+}
+
+/// URL a stored repo was installed from, asking git for older repos without metadata.
+fn entry_remote(meta: &StoreMeta, entry: &StoreEntry) -> Option<String> {
+    if let Some(url) = meta.repo(&entry.key).and_then(|m| m.url.clone()) {
+        return Some(url);
+    }
+    if entry.path.join(".git").exists() {
+        return backend::current().remote_url(&entry.path).ok();
+    }
+    None
+}
+
+/// What installing one repo did.
+#[derive(serde::Serialize)]
+struct Installed {
+    key: String,
+    #[serde(skip)]
+    label: String,
+    path: PathBuf,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pin: Option<String>,
+    existed: bool,
+}
+
+fn describe_install(result: &Result<Installed>) -> String {
+    match result {
+        Ok(i) if i.existed => format!("already installed at {}", i.path.display()),
+        Ok(i) => match &i.pin {
+            Some(pin) => format!("installed {} (pinned at {})", i.label, pin),
+            None => format!("installed {}", i.label),
+        },
+        Err(e) => format!("failed: {:#}", e),
+    }
+}
+
+fn cmd_install(
+    spec: &str,
+    name_override: Option<&str>,
+    strategy: CloneStrategy,
+    trust: TrustPolicy,
+    symlink: bool,
+) -> Result<Installed> {
     ensure_store_dir()?;
+    let items = [(spec.to_string(), ())];
+    let mut results = progress::run(
+        1,
+        &items,
+        |_, status| install_repo(spec, name_override, &strategy, &trust, symlink, status),
+        describe_install,
+    );
+    results.remove(0)
+}
+
+fn install_repo(
+    spec: &str,
+    name_override: Option<&str>,
+    strategy: &CloneStrategy,
+    trust: &TrustPolicy,
+    symlink: bool,
+    status: &progress::Status,
+) -> Result<Installed> {
+    let kind = source::classify(spec, symlink);
+    let (repo, git_ref) = if kind.is_git() {
+        split_repo_ref(spec)
+    } else {
+        (spec, None)
+    };
 
     // Determine repo name
     let name = match name_override {
@@ -144,41 +643,268 @@ fn cmd_install(repo: &str, name_override: Option<&str>) -> Result<()> {
     if name.is_empty() {
         bail!("Could not infer repository name from URL: {}", repo);
     }
+    let name = RepoName::alias(&name)?;
 
-    let dest = dothub_dir().join(&name);
-    if dest.exists() {
-        println!("Repo already exists: {}", dest.display());
-        return Ok(());
+    // Remote git repos are keyed by host/owner/repo so same-named repos don't collide
+    let key = match store::repo_identity(repo).filter(|_| kind == SourceKind::Git) {
+        Some(identity) => RepoName::key(&identity)?,
+        // Everything else sits at the top of the store under its alias
+        None => RepoName::key(&name)?,
+    };
+    let label = if key == name {
+        name.to_string()
+    } else {
+        format!("{} ({})", name, key)
+    };
+
+    let root = dothub_dir();
+    let (meta, index) = load_store()?;
+    let dest = root.join(&key);
+    let existing = |entry: &StoreEntry| Installed {
+        key: entry.key.clone(),
+        label: entry.display_name(),
+        path: entry.path.clone(),
+        pin: None,
+        existed: true,
+    };
+    if let Some(entry) = index.entries.iter().find(|e| e.key == *key) {
+        match entry_remote(&meta, entry) {
+            Some(remote) if !store::same_remote(&remote, repo) => bail!(
+                "{} already holds {}, not {}; pass a different name",
+                dest.display(),
+                remote,
+                repo
+            ),
+            _ => return Ok(existing(entry)),
+        }
+    }
+    if kind.is_git() {
+        // Repos installed before identities existed sit under their short name
+        if let Some(entry) = index.entries.iter().find(|e| {
+            e.key == e.alias && entry_remote(&meta, e).is_some_and(|r| store::same_remote(&r, repo))
+        }) {
+            return Ok(existing(entry));
+        }
+    }
+    // A short name shared with another repo is fine when it was only derived
+    // from the URL: both stay reachable by their full names. Names given on
+    // the command line and top-level keys have to be unique.
+    let shared = index
+        .entries
+        .iter()
+        .find(|e| e.alias == *name || e.key == *name);
+    if let Some(other) = shared {
+        if key == name || name_override.is_some_and(|n| !n.trim().is_empty()) {
+            bail!(
+                "The name {} is already used by {}; pick another: dothub install {} <name>",
+                name,
+                other.key,
+                spec
+            );
+        }
+    }
+
+    // Local directories are recorded by absolute path so updates work from anywhere
+    let location = match kind {
+        SourceKind::Copy | SourceKind::Symlink => fs::canonicalize(repo)
+            .with_context(|| format!("Resolving {}", repo))?
+            .to_string_lossy()
+            .to_string(),
+        SourceKind::Archive if Path::new(repo).is_file() => fs::canonicalize(repo)
+            .with_context(|| format!("Resolving {}", repo))?
+            .to_string_lossy()
+            .to_string(),
+        _ => repo.to_string(),
+    };
+
+    if let Some(parent) = dest.parent() {
+        fs::create_dir_all(parent)
+            .with_context(|| format!("Failed creating {}", parent.display()))?;
+    }
+    let installed = if kind.is_git() {
+        install_git(repo, &dest, git_ref, strategy, trust, status)
+    } else if trust.enforced() {
+        Err(anyhow::anyhow!(
+            "Signature checks only apply to git repositories"
+        ))
+    } else if !strategy.is_full() {
+        Err(anyhow::anyhow!(
+            "--depth, --blobless and --sparse only apply to git repositories"
+        ))
+    } else {
+        status.set(&format!("installing from {}", location));
+        source::install(kind, &location, &root, &dest)
+    };
+    if let Err(e) = installed {
+        store::prune_empty_parents(&root, &dest);
+        return Err(e);
     }
 
-    // Ensure git is available
-    if which::which("git").is_err() {
-        bail!("git is not installed or not found in PATH");
+    StoreMeta::update(&root, |meta| {
+        let entry = meta.repo_mut(&key);
+        entry.alias = (key != name && shared.is_none()).then(|| name.to_string());
+        entry.source = kind;
+        entry.url = Some(location);
+        entry.pin = git_ref.map(str::to_string);
+        entry.clone = strategy.clone();
+        entry.sparse_paths.clear();
+        entry.trust = trust.clone();
+    })?;
+
+    Ok(Installed {
+        key: key.to_string(),
+        label,
+        path: dest,
+        pin: git_ref.map(str::to_string),
+        existed: false,
+    })
+}
+
+fn install_git(
+    repo: &str,
+    dest: &Path,
+    git_ref: Option<&str>,
+    strategy: &CloneStrategy,
+    trust: &TrustPolicy,
+    status: &progress::Status,
+) -> Result<()> {
+    status.set(&format!("cloning into {}", dest.display()));
+    let git = backend::for_strategy(strategy);
+    git.clone_repo(repo, dest, strategy)?;
+
+    if let Some(git_ref) = git_ref {
+        status.set(&format!("checking out {}", git_ref));
+        if let Err(e) = git::run(dest, &["checkout", "--quiet", git_ref]) {
+            let _ = fs::remove_dir_all(dest);
+            return Err(e).with_context(|| format!("Checking out {}", git_ref));
+        }
+        git::update_submodules(dest)?;
     }
 
-    println!("Cloning {} -> {}", repo, dest.display());
-    let status = Command::new("git")
-        .args(["clone", repo, dest.to_string_lossy().as_ref()])
-        .status()
-        .with_context(|| "Failed to spawn git clone")?;
+    if trust.enforced() {
+        status.set("verifying signature");
+        let verified = git
+            .head_commit(dest)
+            .and_then(|head| trust::verify(dest, &head, trust));
+        if let Err(e) = verified {
+            let _ = fs::remove_dir_all(dest);
+            return Err(e);
+        }
+    }
+    Ok(())
+}
+
+fn cmd_pin(name: &str, git_ref: &str) -> Result<()> {
+    git::ensure_available()?;
+    let entry = resolve_repo(name)?;
+
+    // The ref may be newer than the last pull, so fetch before checking it out
+    if let Err(e) = git::run(&entry.path, &["fetch", "--quiet", "--tags", "origin"]) {
+        eprintln!("Warning: {:#}", e);
+    }
+    pin_checkout(&entry, git_ref)?;
 
-    if !status.success() {
-        bail!("git clone failed with status: {}", status);
+    println!("Pinned {} at {}", entry.alias, git_ref);
+    Ok(())
+}
+
+/// Check out `git_ref` (if the trust policy accepts it) and record it as the pin.
+fn pin_checkout(entry: &StoreEntry, git_ref: &str) -> Result<()> {
+    let path = &entry.path;
+    let root = dothub_dir();
+    let mut meta = StoreMeta::load(&root)?;
+    if let Some(policy) = meta
+        .repo(&entry.key)
+        .map(|m| &m.trust)
+        .filter(|t| t.enforced())
+    {
+        // `git checkout <branch>` also finds branches that only exist as
+        // origin/<branch>, so look there too
+        let commit = git::output(path, &["rev-parse", &format!("{}^{{commit}}", git_ref)])
+            .or_else(|_| {
+                git::output(
+                    path,
+                    &["rev-parse", &format!("origin/{}^{{commit}}", git_ref)],
+                )
+            })?;
+        trust::verify(path, &commit, policy)?;
+    }
+    git::run(path, &["checkout", "--quiet", git_ref])?;
+    git::update_submodules(path)?;
+
+    meta.repo_mut(&entry.key).pin = Some(git_ref.to_string());
+    meta.save(&root)
+}
+
+fn cmd_unpin(name: &str) -> Result<()> {
+    git::ensure_available()?;
+    let entry = resolve_repo(name)?;
+    let path = &entry.path;
+
+    let root = dothub_dir();
+    let mut meta = StoreMeta::load(&root)?;
+    if meta.repo(&entry.key).and_then(|m| m.pin.as_ref()).is_none() {
+        println!("{} is not pinned", entry.alias);
+        return Ok(());
     }
 
-    println!("Installed {}", name);
+    let branch = match meta.repo(&entry.key).and_then(|m| m.fork.as_ref()) {
+        Some(fork) => fork.branch.clone(),
+        None => git::default_branch(path)?,
+    };
+    git::run(path, &["checkout", "--quiet", &branch])?;
+    git::update_submodules(path)?;
+    meta.repo_mut(&entry.key).pin = None;
+    meta.save(&root)?;
+
+    println!("Unpinned {}; now tracking {}", entry.alias, branch);
     Ok(())
 }
 
-fn cmd_link(name: &str, target_name: &str) -> Result<()> {
-    let source = dothub_dir().join(name);
+/// A link `dothub link` created.
+#[derive(serde::Serialize)]
+struct Linked {
+    target: PathBuf,
+    source: PathBuf,
+    /// Where what was at `target` got moved
+    #[serde(skip_serializing_if = "Option::is_none")]
+    backup: Option<PathBuf>,
+}
+
+fn cmd_link(name: &str, subpath: Option<&str>, target_name: &str, backup: bool) -> Result<Linked> {
+    let entry = resolve_repo(name)?;
+    let repo = entry.path;
+    let subpath = subpath
+        .map(|p| p.trim_matches('/'))
+        .filter(|p| !p.is_empty());
+    if let Some(sub) = subpath {
+        if Path::new(sub)
+            .components()
+            .any(|c| !matches!(c, std::path::Component::Normal(_)))
+        {
+            bail!(Failure::new(
+                ErrorKind::InvalidInput,
+                format!("Subpath must be relative to the repo root: {}", sub)
+            ));
+        }
+    }
+    widen_sparse_checkout(&entry.key, &repo, subpath)?;
+
+    let source = match subpath {
+        Some(sub) => repo.join(sub),
+        None => repo,
+    };
     if !source.exists() {
-        bail!("Source repo not found: {}", source.display());
+        bail!(Failure::new(
+            ErrorKind::NotFound,
+            format!("Source repo not found: {}", source.display())
+        ));
     }
 
     // Target: ~/.config/<target_name>
     let home = dirs::home_dir().context("Unable to determine home directory")?;
     let config_dir = home.join(".config");
+    store::check_relative("Link target", target_name)?;
     let target = config_dir.join(target_name);
 
     if !config_dir.exists() {
@@ -186,9 +912,30 @@ fn cmd_link(name: &str, target_name: &str) -> Result<()> {
             .with_context(|| format!("Failed creating {}", config_dir.display()))?;
     }
 
+    let mut moved_to = None;
     if target.exists() || symlink_exists(&target) {
-        remove_path(&target)
-            .with_context(|| format!("Failed removing existing {}", target.display()))?;
+        store::ensure_inside(&config_dir, &target)?;
+        if backup && !points_into_store(&target) {
+            let backup_path = backup_path_for(&target);
+            fs::rename(&target, &backup_path).with_context(|| {
+                format!(
+                    "Failed moving {} to {}",
+                    target.display(),
+                    backup_path.display()
+                )
+            })?;
+            if !output::structured() {
+                println!(
+                    "Backed up {} -> {}",
+                    target.display(),
+                    backup_path.display()
+                );
+            }
+            moved_to = Some(backup_path);
+        } else {
+            remove_path(&target)
+                .with_context(|| format!("Failed removing existing {}", target.display()))?;
+        }
     }
 
     // Create symlink
@@ -223,51 +970,509 @@ fn cmd_link(name: &str, target_name: &str) -> Result<()> {
         }
     }
 
-    println!("Linked {} -> {}", source.display(), target.display());
-    Ok(())
+    if !output::structured() {
+        println!("Linked {} -> {}", source.display(), target.display());
+    }
+    Ok(Linked {
+        target,
+        source,
+        backup: moved_to,
+    })
 }
 
-fn cmd_update() -> Result<()> {
-    ensure_store_dir()?;
-    if which::which("git").is_err() {
-        bail!("git is not installed or not found in PATH");
+/// Outcome of updating one repo.
+enum UpdateOutcome {
+    Updated {
+        from: String,
+        to: String,
+        files: usize,
+    },
+    Current,
+    Pinned(String),
+    /// The upstream commit fails the repo's trust policy
+    Refused(String),
+    /// Upstream could not be rebased or merged into a fork branch
+    Conflict(String),
+    Refreshed,
+    Symlinked(String),
+    Skipped(&'static str),
+}
+
+struct UpdateReport {
+    outcome: Result<UpdateOutcome>,
+    /// How local changes in the way were handled
+    local: Option<local::Applied>,
+    submodule_error: Option<String>,
+    changelog: Option<history::UpdateRecord>,
+    /// Why the changelog could not be added to the repo's history
+    history_error: Option<String>,
+}
+
+impl UpdateReport {
+    fn result_and_details(&self) -> (&'static str, String) {
+        let (result, mut details) = match &self.outcome {
+            Ok(UpdateOutcome::Updated { from, to, files }) => (
+                "updated",
+                format!(
+                    "{}..{}, {} files changed",
+                    short_commit(from),
+                    short_commit(to),
+                    files
+                ),
+            ),
+            Ok(UpdateOutcome::Current) => ("already current", String::new()),
+            Ok(UpdateOutcome::Pinned(pin)) => {
+                ("pinned", format!("fetched only, pinned at {}", pin))
+            }
+            Ok(UpdateOutcome::Refused(why)) => ("refused", why.clone()),
+            Ok(UpdateOutcome::Conflict(how)) => ("conflict", how.clone()),
+            Ok(UpdateOutcome::Refreshed) => ("updated", "refreshed from source".to_string()),
+            Ok(UpdateOutcome::Symlinked(to)) => ("symlinked", format!("follows {}", to)),
+            Ok(UpdateOutcome::Skipped(why)) => ("skipped", why.to_string()),
+            Err(e) => ("failed", format!("{:#}", e)),
+        };
+        if let Some(applied) = &self.local {
+            if !details.is_empty() {
+                details.push_str("; ");
+            }
+            details.push_str(&format!("{}: {}", applied.strategy.name(), applied.message));
+        }
+        let result = match &self.local {
+            Some(applied) if applied.conflicts => "conflict",
+            _ => result,
+        };
+        if let Some(e) = &self.submodule_error {
+            if !details.is_empty() {
+                details.push_str("; ");
+            }
+            details.push_str(&format!("submodules failed: {}", e));
+        }
+        if let Some(e) = &self.history_error {
+            if !details.is_empty() {
+                details.push_str("; ");
+            }
+            details.push_str(&format!("history not saved: {}", e));
+        }
+        (result, details)
+    }
+}
+
+/// One repo's update for `--format json|yaml`.
+#[derive(serde::Serialize)]
+struct UpdateDocument<'a> {
+    name: &'a str,
+    key: &'a str,
+    result: &'static str,
+    #[serde(skip_serializing_if = "String::is_empty")]
+    details: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    from: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    to: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    local: Option<&'a local::Applied>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    submodule_error: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    changelog: Option<&'a history::UpdateRecord>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    history_error: Option<&'a str>,
+}
+
+impl UpdateReport {
+    fn document<'a>(&'a self, entry: &'a StoreEntry) -> UpdateDocument<'a> {
+        let (result, details) = self.result_and_details();
+        let (from, to) = match &self.outcome {
+            Ok(UpdateOutcome::Updated { from, to, .. }) => (Some(from.as_str()), Some(to.as_str())),
+            _ => (None, None),
+        };
+        UpdateDocument {
+            name: &entry.alias,
+            key: &entry.key,
+            result,
+            details,
+            from,
+            to,
+            local: self.local.as_ref(),
+            submodule_error: self.submodule_error.as_deref(),
+            changelog: self.changelog.as_ref(),
+            history_error: self.history_error.as_deref(),
+        }
     }
+}
+
+fn describe_update(report: &UpdateReport) -> String {
+    match report.result_and_details() {
+        (result, details) if details.is_empty() => result.to_string(),
+        (result, details) => format!("{} ({})", result, details),
+    }
+}
+
+fn cmd_update(
+    names: &[String],
+    linked_only: bool,
+    on_local_changes: Option<LocalChanges>,
+    write_lock: bool,
+    jobs: usize,
+) -> Result<()> {
+    ensure_store_dir()?;
+    backend::current().ensure_available()?;
+    let default_strategy = config::Config::load_or_default()?.settings.on_local_changes;
 
     let root = dothub_dir();
-    let mut updated = 0usize;
-    let mut skipped = 0usize;
+    let (meta, index) = load_store()?;
+    if index.entries.is_empty() && !output::structured() {
+        println!("No repositories installed in {}.", root.display());
+        return Ok(());
+    }
+    let mut selected = if names.is_empty() {
+        index.entries
+    } else {
+        index.select(names)?
+    };
+    if linked_only {
+        let links = active_links()?;
+        selected.retain(|e| {
+            let repo = e.path.canonicalize().unwrap_or_else(|_| e.path.clone());
+            links.iter().any(|(_, target)| target.starts_with(&repo))
+        });
+        if selected.is_empty() && !output::structured() {
+            println!("No selected repository is linked from ~/.config.");
+            return Ok(());
+        }
+    }
+    let items: Vec<(String, StoreEntry)> =
+        selected.into_iter().map(|e| (e.alias.clone(), e)).collect();
+
+    let reports = progress::run(
+        jobs,
+        &items,
+        |entry, status| {
+            let repo_meta = meta.repo(&entry.key);
+            let strategy = on_local_changes
+                .or(repo_meta.and_then(|m| m.on_local_changes))
+                .unwrap_or(default_strategy);
+            update_repo(&root, entry, repo_meta, strategy, status)
+        },
+        describe_update,
+    );
+    if output::structured() {
+        let docs: Vec<UpdateDocument> = items
+            .iter()
+            .zip(&reports)
+            .map(|((_, entry), report)| report.document(entry))
+            .collect();
+        output::emit(&docs)?;
+        if write_lock {
+            lock::write(None)?;
+        }
+        return Ok(());
+    }
 
-    for entry in fs::read_dir(&root).with_context(|| format!("Reading {}", root.display()))? {
-        let entry = entry?;
-        let path = entry.path();
-        if !path.is_dir() {
-            continue;
+    let mut table = styled_table();
+    table.set_header(["Repo", "Result", "Details"]);
+    let mut counts: HashMap<&str, usize> = HashMap::new();
+    let mut submodule_failures = 0usize;
+    for ((name, _), report) in items.iter().zip(&reports) {
+        let (result, details) = report.result_and_details();
+        *counts.entry(result).or_default() += 1;
+        if report.submodule_error.is_some() {
+            submodule_failures += 1;
         }
-        if !path.join(".git").exists() {
-            skipped += 1;
-            continue;
+        table.add_row(vec![name.clone(), result.to_string(), details]);
+    }
+    println!("{}", table);
+
+    let count = |k: &str| counts.get(k).copied().unwrap_or(0);
+    println!(
+        "Updated {} repositories (already current {}, pinned {}, refused {}, conflict {}, symlinked {}, skipped {}, failed {}).",
+        count("updated"),
+        count("already current"),
+        count("pinned"),
+        count("refused"),
+        count("conflict"),
+        count("symlinked"),
+        count("skipped"),
+        count("failed")
+    );
+    if submodule_failures > 0 {
+        println!(
+            "Submodule updates failed in {} repositories.",
+            submodule_failures
+        );
+    }
+    for ((name, _), report) in items.iter().zip(&reports) {
+        if let Some(record) = &report.changelog {
+            println!();
+            history::print(name, record);
         }
+    }
+    if write_lock {
+        lock::write(None)?;
+    }
+    Ok(())
+}
 
-        println!("Updating {}", path.display());
-        let status = Command::new("git")
-            .args(["-C", path.to_string_lossy().as_ref(), "pull", "--ff-only"])
-            .status()
-            .with_context(|| format!("Running git pull in {}", path.display()))?;
-        if status.success() {
-            updated += 1;
-        } else {
-            eprintln!(
-                "git pull failed in {} with status {}",
-                path.display(),
-                status
-            );
+fn update_repo(
+    root: &Path,
+    entry: &StoreEntry,
+    repo_meta: Option<&RepoMeta>,
+    strategy: LocalChanges,
+    status: &progress::Status,
+) -> UpdateReport {
+    let mut local = None;
+    let mut submodule_error = None;
+    let outcome = pull_repo(
+        root,
+        entry,
+        repo_meta,
+        strategy,
+        status,
+        &mut local,
+        &mut submodule_error,
+    );
+    let mut history_error = None;
+    let changelog = match &outcome {
+        Ok(UpdateOutcome::Updated { from, to, .. }) => {
+            let record = history::changelog(&entry.path, from, to);
+            // Printing here would break the progress board; the report says it
+            if let Err(e) = history::record(root, &entry.key, &record) {
+                history_error = Some(format!("{:#}", e));
+            }
+            Some(record)
         }
+        _ => None,
+    };
+    UpdateReport {
+        outcome,
+        local,
+        submodule_error,
+        changelog,
+        history_error,
     }
+}
 
-    println!("Updated {} repositories (skipped {}).", updated, skipped);
+fn pull_repo(
+    root: &Path,
+    entry: &StoreEntry,
+    repo_meta: Option<&RepoMeta>,
+    strategy: LocalChanges,
+    status: &progress::Status,
+    local: &mut Option<local::Applied>,
+    submodule_error: &mut Option<String>,
+) -> Result<UpdateOutcome> {
+    let path = &entry.path;
+    let kind = repo_meta.map(|m| m.source).unwrap_or_default();
+    if !kind.is_git() {
+        let location = repo_meta.and_then(|m| m.url.as_deref()).unwrap_or_default();
+        if kind == SourceKind::Symlink {
+            return Ok(UpdateOutcome::Symlinked(location.to_string()));
+        }
+        status.set(&format!("refreshing from {}", location));
+        source::refresh(kind, location, root, path)?;
+        return Ok(UpdateOutcome::Refreshed);
+    }
+    if !path.join(".git").exists() {
+        return Ok(UpdateOutcome::Skipped("not a git repository"));
+    }
+
+    let git = match repo_meta {
+        Some(m) => backend::for_strategy(&m.clone),
+        None => backend::current(),
+    };
+    status.set("fetching");
+    git.fetch(path)
+        .with_context(|| format!("Fetching with the {} backend", git.name()))?;
+    // Pinned repos only learn about new upstream commits; the worktree stays put
+    if let Some(pin) = repo_meta.and_then(|m| m.pin.as_deref()) {
+        return Ok(UpdateOutcome::Pinned(pin.to_string()));
+    }
+
+    let state = git.status(path)?;
+    if state.branch.is_none() {
+        return Ok(UpdateOutcome::Skipped("HEAD is detached"));
+    }
+    let fork = repo_meta.and_then(|m| m.fork.as_ref());
+    if fork.is_some() && fork::is_current(path) {
+        return Ok(UpdateOutcome::Current);
+    }
+    if let Some(policy) = repo_meta.map(|m| &m.trust).filter(|t| t.enforced()) {
+        status.set("verifying signature");
+        let upstream = git.upstream_commit(path)?;
+        if upstream != git.head_commit(path)? {
+            if let Some(reason) = trust::rejection(path, &upstream, policy)? {
+                return Ok(UpdateOutcome::Refused(reason));
+            }
+        }
+    }
+
+    // Local changes only matter when upstream has something to bring in
+    let (ahead, behind) = git.ahead_behind(path)?;
+    let in_the_way = if state.dirty {
+        Some("local modifications")
+    } else if ahead > 0 && fork.is_none() {
+        Some("local commits")
+    } else {
+        None
+    };
+    let mut stashed = false;
+    let mut rebase_fork = None;
+    if let Some(why) = in_the_way.filter(|_| behind > 0) {
+        match strategy {
+            LocalChanges::Refuse => {}
+            LocalChanges::Skip => {
+                *local = Some(local::Applied::new(strategy, "left as is".to_string()));
+                return Ok(UpdateOutcome::Skipped(why));
+            }
+            LocalChanges::ResetToUpstream => {
+                status.set("saving local changes");
+                let from = git.head_commit(path)?;
+                let patch = local::reset_to_upstream(root, &entry.key, path)?;
+                let to = git.head_commit(path)?;
+                let files = git::output(path, &["diff", "--name-only", &from, &to])?
+                    .lines()
+                    .count();
+                *local = Some(local::Applied::new(
+                    strategy,
+                    format!(
+                        "local changes saved to {}; apply them again with git apply",
+                        patch.display()
+                    ),
+                ));
+                return finish_pull(path, repo_meta, status, submodule_error)
+                    .map(|_| UpdateOutcome::Updated { from, to, files });
+            }
+            LocalChanges::Autostash | LocalChanges::Rebase => {
+                if state.dirty {
+                    status.set("stashing local changes");
+                    local::stash(path)?;
+                    stashed = true;
+                }
+                // A fork already rebases or merges its own way
+                if strategy == LocalChanges::Rebase && fork.is_none() {
+                    rebase_fork = state.branch.clone().map(|branch| Fork {
+                        branch,
+                        integrate: Integrate::Rebase,
+                    });
+                }
+            }
+        }
+    }
+    let dirty = state.dirty && !stashed;
+
+    let pulled = match fork.or(rebase_fork.as_ref()) {
+        Some(fork) => {
+            status.set(&format!("bringing upstream into {}", fork.branch));
+            fork::integrate(path, fork, dirty).map(|integrated| match integrated {
+                fork::Integrated::Current => UpdateOutcome::Current,
+                fork::Integrated::Moved { from, to, files } => {
+                    UpdateOutcome::Updated { from, to, files }
+                }
+                fork::Integrated::Conflict(how) => UpdateOutcome::Conflict(how),
+            })
+        }
+        None => {
+            status.set("fast-forwarding");
+            git.fast_forward(path)
+                .map_err(|e| {
+                    if dirty {
+                        e.context("The worktree has local modifications")
+                    } else {
+                        e
+                    }
+                })
+                .map(|ff| {
+                    if ff.is_noop() {
+                        UpdateOutcome::Current
+                    } else {
+                        UpdateOutcome::Updated {
+                            files: ff.changed_files.len(),
+                            from: ff.from,
+                            to: ff.to,
+                        }
+                    }
+                })
+        }
+    };
+    let rebased =
+        (rebase_fork.is_some() && ahead > 0 && matches!(pulled, Ok(UpdateOutcome::Updated { .. })))
+            .then(|| format!("{} local commits rebased onto upstream", ahead));
+    // Local changes go back even when the pull failed
+    if stashed {
+        status.set("restoring local changes");
+        let conflicts = local::unstash(path)?;
+        let mut applied = local::unstashed(strategy, path, conflicts);
+        if let Some(rebased) = rebased {
+            applied.message = format!("{}, {}", rebased, applied.message);
+        }
+        *local = Some(applied);
+    } else if let Some(rebased) = rebased {
+        *local = Some(local::Applied::new(strategy, rebased));
+    }
+    let outcome = pulled?;
+    if matches!(outcome, UpdateOutcome::Conflict(_)) {
+        return Ok(outcome);
+    }
+    finish_pull(path, repo_meta, status, submodule_error)?;
+    Ok(outcome)
+}
+
+/// Sparse checkout and submodules follow whatever commit a pull landed on.
+fn finish_pull(
+    path: &Path,
+    repo_meta: Option<&RepoMeta>,
+    status: &progress::Status,
+    submodule_error: &mut Option<String>,
+) -> Result<()> {
+    if let Some(m) = repo_meta.filter(|m| m.clone.sparse) {
+        let mut args = vec!["sparse-checkout", "set"];
+        args.extend(m.sparse_paths.iter().map(String::as_str));
+        git::run(path, &args)?;
+    }
+    status.set("updating submodules");
+    if let Err(e) = git::update_submodules(path) {
+        *submodule_error = Some(format!("{:#}", e));
+    }
     Ok(())
 }
 
+fn short_commit(commit: &str) -> &str {
+    &commit[..commit.len().min(7)]
+}
+
+fn styled_table() -> Table {
+    let mut table = Table::new();
+    table
+        .load_preset(UTF8_BORDERS_ONLY)
+        .apply_modifier(UTF8_ROUND_CORNERS);
+    table
+}
+
+fn cmd_sync(args: SyncArgs) -> Result<()> {
+    if args.locked {
+        lock::cmd_sync_locked(args.file.as_deref(), args.jobs)
+    } else {
+        sync::cmd_sync(args.file.as_deref(), args.prune, args.dry_run, args.jobs)
+    }
+}
+
+fn cmd_schedule(cmd: ScheduleCmd) -> Result<()> {
+    match cmd {
+        ScheduleCmd::Enable(args) => {
+            let every = if args.hourly {
+                schedule::Every::Hour
+            } else if args.weekly {
+                schedule::Every::Week
+            } else {
+                schedule::Every::Day
+            };
+            schedule::cmd_enable(every, args.on_local_changes, args.cron)
+        }
+        ScheduleCmd::Disable => schedule::cmd_disable(),
+        ScheduleCmd::Status => schedule::cmd_status(),
+    }
+}
+
 fn cmd_completions(shell: Shell) -> Result<()> {
     use clap::CommandFactory;
     let mut cmd = Cli::command();
@@ -282,6 +1487,78 @@ fn cmd_completions(shell: Shell) -> Result<()> {
     Ok(())
 }
 
+/// Make sure a sparse clone has `subpath` checked out before it gets linked.
+/// Linking the repo root turns sparse checkout off entirely.
+fn widen_sparse_checkout(key: &str, repo: &Path, subpath: Option<&str>) -> Result<()> {
+    let root = dothub_dir();
+    let mut meta = StoreMeta::load(&root)?;
+    let name = entry_alias(&meta, key);
+    let entry = match meta.repos.get_mut(key) {
+        Some(m) if m.clone.sparse => m,
+        _ => return Ok(()),
+    };
+    match subpath {
+        Some(sub) => {
+            if entry.sparse_paths.iter().any(|p| p == sub) {
+                return Ok(());
+            }
+            if !output::structured() {
+                println!("Adding {} to the sparse checkout of {}", sub, name);
+            }
+            git::run(repo, &["sparse-checkout", "add", sub])?;
+            entry.sparse_paths.push(sub.to_string());
+        }
+        None => {
+            if !output::structured() {
+                println!(
+                    "Linking the whole repo; disabling sparse checkout of {}",
+                    name
+                );
+            }
+            git::run(repo, &["sparse-checkout", "disable"])?;
+            entry.clone.sparse = false;
+            entry.sparse_paths.clear();
+        }
+    }
+    meta.save(&root)
+}
+
+fn entry_alias(meta: &StoreMeta, key: &str) -> String {
+    meta.repo(key)
+        .and_then(|m| m.alias.clone())
+        .unwrap_or_else(|| key.rsplit('/').next().unwrap_or(key).to_string())
+}
+
+/// Next free `<target>.dothub-bak-<unix time>` path for moving a config aside.
+fn backup_path_for(target: &Path) -> PathBuf {
+    let stamp = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    let file_name = target
+        .file_name()
+        .map(|s| s.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let mut candidate = target.with_file_name(format!("{}.dothub-bak-{}", file_name, stamp));
+    let mut n = 1;
+    while candidate.exists() || symlink_exists(&candidate) {
+        candidate = target.with_file_name(format!("{}.dothub-bak-{}-{}", file_name, stamp, n));
+        n += 1;
+    }
+    candidate
+}
+
+/// True when `path` is a symlink resolving into the dothub store.
+fn points_into_store(path: &Path) -> bool {
+    if !symlink_exists(path) {
+        return false;
+    }
+    let resolved = path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+    let store = dothub_dir();
+    let store = store.canonicalize().unwrap_or(store);
+    resolved.starts_with(&store)
+}
+
 fn symlink_exists(path: &Path) -> bool {
     match fs::symlink_metadata(path) {
         Ok(md) => md.file_type().is_symlink(),
@@ -304,15 +1581,15 @@ fn remove_path(path: &Path) -> Result<()> {
     }
 }
 
-fn cmd_active() -> Result<()> {
+/// Symlinks in ~/.config that resolve into the store, as (name, resolved target).
+fn active_links() -> Result<Vec<(String, PathBuf)>> {
     let home = dirs::home_dir().context("Unable to determine home directory")?;
     let config_dir = home.join(".config");
+    let mut found = Vec::new();
     if !config_dir.exists() {
-        println!("No ~/.config directory found.");
-        return Ok(());
+        return Ok(found);
     }
 
-    let mut found = Vec::new();
     for entry in
         fs::read_dir(&config_dir).with_context(|| format!("Reading {}", config_dir.display()))?
     {
@@ -351,7 +1628,31 @@ fn cmd_active() -> Result<()> {
             found.push((name, resolved));
         }
     }
+    Ok(found)
+}
+
+/// A symlink in ~/.config that points into the store.
+#[derive(serde::Serialize)]
+struct ActiveLink {
+    name: String,
+    target: PathBuf,
+}
+
+fn cmd_active() -> Result<()> {
+    let home = dirs::home_dir().context("Unable to determine home directory")?;
+    if output::structured() {
+        let links: Vec<ActiveLink> = active_links()?
+            .into_iter()
+            .map(|(name, target)| ActiveLink { name, target })
+            .collect();
+        return output::emit(&links);
+    }
+    if !home.join(".config").exists() {
+        println!("No ~/.config directory found.");
+        return Ok(());
+    }
 
+    let found = active_links()?;
     if found.is_empty() {
         println!("No active dothub links in ~/.config.");
     } else {
@@ -362,49 +1663,60 @@ fn cmd_active() -> Result<()> {
     Ok(())
 }
 
-fn cmd_list() -> Result<()> {
-    ensure_store_dir()?;
-    let root = dothub_dir();
-    let mut repos: Vec<String> = Vec::new();
-    for entry in fs::read_dir(&root).with_context(|| format!("Reading {}", root.display()))? {
-        let entry = entry?;
-        let path = entry.path();
-        if !path.is_dir() {
-            continue;
-        }
-        let name = match path.file_name().and_then(|s| s.to_str()) {
-            Some(s) => s.to_string(),
-            None => continue,
-        };
-        repos.push(name);
+/// A repo `dothub remove` moved to the trash.
+#[derive(serde::Serialize)]
+struct Removed {
+    key: String,
+    path: PathBuf,
+    /// Entry to pass to `dothub trash restore`
+    trash: String,
+}
+
+/// Ask a yes/no question on the terminal; `yes` answers it up front. Without
+/// a terminal there is nobody to ask, so `yes` is required.
+fn confirm(question: &str, yes: bool) -> Result<bool> {
+    if yes {
+        return Ok(true);
     }
-    repos.sort();
-    if repos.is_empty() {
-        println!("No repositories installed in {}.", root.display());
-    } else {
-        for r in repos {
-            println!("{}", r);
-        }
+    if !io::stdin().is_terminal() {
+        bail!(Failure::new(
+            ErrorKind::InvalidInput,
+            format!("{} Pass --yes to confirm without a terminal", question)
+        ));
     }
-    Ok(())
+    eprint!("{} [y/N] ", question);
+    io::stderr().flush()?;
+    let mut answer = String::new();
+    io::stdin().read_line(&mut answer)?;
+    Ok(matches!(answer.trim(), "y" | "Y" | "yes" | "Yes"))
 }
 
-fn cmd_remove(name: &str) -> Result<()> {
-    ensure_store_dir()?;
-    let path = dothub_dir().join(name);
-    if !path.exists() {
-        bail!("Repository not found: {}", path.display());
+fn cmd_remove(name: &str, yes: bool) -> Result<Removed> {
+    let entry = resolve_repo(name)?;
+    let path = &entry.path;
+    let root = dothub_dir();
+    store::ensure_inside(&root, path)?;
+    if !confirm(&format!("Move {} to the trash?", entry.display_name()), yes)? {
+        bail!("Cancelled; {} was left in place", entry.display_name());
     }
-    let md = fs::symlink_metadata(&path).with_context(|| format!("stat {}", path.display()))?;
-    if md.is_dir() {
-        fs::remove_dir_all(&path)
-            .with_context(|| format!("Removing directory {}", path.display()))?;
-    } else {
-        fs::remove_file(&path)
-            .with_context(|| format!("Removing file {}", path.display()))?;
+    let meta = StoreMeta::load(&root)?;
+    let trashed = trash::put(&root, &entry, meta.repo(&entry.key).cloned())?;
+    store::prune_empty_parents(&root, path);
+    StoreMeta::update(&root, |meta| {
+        meta.repos.remove(&entry.key);
+    })?;
+    if !output::structured() {
+        println!(
+            "Moved {} to the trash; dothub trash restore {} brings it back",
+            entry.display_name(),
+            entry.alias
+        );
     }
-    println!("Removed {}", name);
-    Ok(())
+    Ok(Removed {
+        key: entry.key,
+        path: entry.path,
+        trash: trashed.id,
+    })
 }
 
 #[derive(Debug, serde::Deserialize)]
@@ -414,17 +1726,22 @@ enum FlexEntry {
     Many(Vec<String>),
 }
 
-fn cmd_hub(types: Vec<String>, url: Option<String>) -> Result<()> {
-    // Print ASCII banner at the top
-    println!("{}", ASCII_BANNER);
-    let url = url.as_deref().unwrap_or(DEFAULT_HUB_URL);
-    let yaml = match fetch_text(url) {
-        Ok(text) => text,
-        Err(_) => {
-            eprintln!("\x1b[31mFailed to fetch the hub file. Please ensure you have internet connection.\x1b[0m");
-            std::process::exit(1);
-        }
-    };
+struct HubEntry {
+    ty: String,
+    link: String,
+    stars: u64,
+}
+
+/// Hub entries ranked by stars, plus how the star counts were obtained.
+struct RankedHub {
+    entries: Vec<HubEntry>,
+    token_present: bool,
+    graphql_failed: bool,
+}
+
+fn fetch_hub_items(url: Option<&str>, types: &[String]) -> Result<Vec<(String, String)>> {
+    let url = url.unwrap_or(DEFAULT_HUB_URL);
+    let yaml = fetch_text(url)?;
 
     let map: HashMap<String, FlexEntry> =
         serde_yaml::from_str(&yaml).context("Parsing YAML for hub")?;
@@ -446,65 +1763,132 @@ fn cmd_hub(types: Vec<String>, url: Option<String>) -> Result<()> {
             }
         }
     }
+    Ok(items)
+}
 
+fn rank_hub_items(items: Vec<(String, String)>) -> RankedHub {
     // Collect stars efficiently (GraphQL when token present; REST fallback otherwise)
     let token = env::var("GITHUB_TOKEN").ok();
-    let mut warn_graphql_failed = false;
+    let mut graphql_failed = false;
     // Show a spinner during star fetching
     let spinner_stop = start_spinner("Downloading stars from github..");
 
-    let mut detailed: Vec<(String, String, u64)> = Vec::with_capacity(items.len());
+    let mut entries: Vec<HubEntry> = Vec::with_capacity(items.len());
     if let Some(ref t) = token {
         let links_only: Vec<String> = items.iter().map(|(_, l)| l.clone()).collect();
         match github_stars_batch(&links_only, Some(t.as_str())) {
             Ok(stars_map) => {
                 for (ty, link) in items {
                     let stars = *stars_map.get(&link).unwrap_or(&0);
-                    detailed.push((ty, link, stars));
+                    entries.push(HubEntry { ty, link, stars });
                 }
             }
             Err(_) => {
-                warn_graphql_failed = true;
+                graphql_failed = true;
                 for (ty, link) in items {
                     let stars = github_stars(&link).unwrap_or(0);
-                    detailed.push((ty, link, stars));
+                    entries.push(HubEntry { ty, link, stars });
                 }
             }
         }
     } else {
         for (ty, link) in items {
             let stars = github_stars(&link).unwrap_or(0);
-            detailed.push((ty, link, stars));
+            entries.push(HubEntry { ty, link, stars });
         }
     }
 
     spinner_stop.store(true, Ordering::SeqCst);
     // Leave the last line in place; print a newline to cleanly end spinner
-    eprintln!("");
+    eprintln!();
 
     // Sort by stars desc
-    detailed.sort_by(|a, b| b.2.cmp(&a.2));
+    entries.sort_by_key(|e| std::cmp::Reverse(e.stars));
 
-    let mut table = Table::new();
-    table.load_preset(UTF8_BORDERS_ONLY).apply_modifier(UTF8_ROUND_CORNERS);
-    table.set_header(["#", "Stars", "Installed", "Source"]);
+    RankedHub {
+        entries,
+        token_present: token.is_some(),
+        graphql_failed,
+    }
+}
+
+/// A hub entry for `--format json|yaml`.
+#[derive(serde::Serialize)]
+struct HubRow {
+    rank: usize,
+    #[serde(rename = "type")]
+    ty: String,
+    stars: u64,
+    installed: bool,
+    source: String,
+}
+
+fn cmd_hub(types: Vec<String>, url: Option<String>) -> Result<()> {
+    let structured = output::structured();
+    if !structured {
+        // Print ASCII banner at the top
+        println!("{}", ASCII_BANNER);
+    }
+    let items = match fetch_hub_items(url.as_deref(), &types) {
+        Ok(items) => items,
+        Err(e) if e.downcast_ref::<serde_yaml::Error>().is_some() => return Err(e),
+        Err(e) if structured => {
+            return Err(e.context(Failure::new(
+                ErrorKind::Network,
+                "Failed to fetch the hub file",
+            )))
+        }
+        Err(_) => {
+            eprintln!("\x1b[31mFailed to fetch the hub file. Please ensure you have internet connection.\x1b[0m");
+            std::process::exit(1);
+        }
+    };
 
-    for (idx, (_ty, link, stars)) in detailed.into_iter().enumerate() {
-        let rank = (idx + 1).to_string();
-        let name = derive_repo_name(&link);
-        let installed = dothub_dir().join(&name).exists();
-        let installed_str = if installed { "y" } else { "n" };
-        table.add_row(vec![rank, stars.to_string(), installed_str.to_string(), link]);
+    let ranked = rank_hub_items(items);
+
+    let store = if dothub_dir().exists() {
+        load_store().ok().map(|(_, index)| index)
+    } else {
+        None
+    };
+    let rows: Vec<HubRow> = ranked
+        .entries
+        .into_iter()
+        .enumerate()
+        .map(|(idx, entry)| HubRow {
+            rank: idx + 1,
+            installed: store
+                .as_ref()
+                .is_some_and(|index| hub_entry_installed(index, &entry.link)),
+            ty: entry.ty,
+            stars: entry.stars,
+            source: entry.link,
+        })
+        .collect();
+    if structured {
+        return output::emit(&rows);
+    }
+
+    let mut table = styled_table();
+    table.set_header(["#", "Stars", "Installed", "Source"]);
+    for row in rows {
+        let installed_str = if row.installed { "y" } else { "n" };
+        table.add_row(vec![
+            row.rank.to_string(),
+            row.stars.to_string(),
+            installed_str.to_string(),
+            row.source,
+        ]);
     }
 
     println!("{}", table);
-    if token.is_none() {
+    if !ranked.token_present {
         println!(
             "\x1b[33mTo improve performance, please set your GITHUB_TOKEN environment variable.\nLearn more: {}\x1b[0m",
             GH_TOKEN_HELP_URL
         );
     }
-    if warn_graphql_failed {
+    if ranked.graphql_failed {
         println!(
             "\x1b[33mGITHUB_TOKEN detected but GitHub GraphQL failed; falling back to REST.\nLearn more: {}\x1b[0m",
             GH_TOKEN_HELP_URL
@@ -516,6 +1900,66 @@ fn cmd_hub(types: Vec<String>, url: Option<String>) -> Result<()> {
     Ok(())
 }
 
+fn hub_entry_installed(index: &StoreIndex, link: &str) -> bool {
+    index.entries.iter().any(|e| hub_link_matches(e, link))
+}
+
+/// Whether a hub link refers to an installed repo.
+fn hub_link_matches(entry: &StoreEntry, link: &str) -> bool {
+    store::repo_identity(link).as_ref() == Some(&entry.key) || entry.key == derive_repo_name(link)
+}
+
+fn cmd_get(entry: &str, selector: Option<&str>, link: bool, url: Option<String>) -> Result<()> {
+    let (ty, selector) = match selector {
+        Some(sel) => (entry, sel),
+        None => entry
+            .split_once('/')
+            .with_context(|| format!("Expected <type> <rank> or <type>/<name>, got: {}", entry))?,
+    };
+    if ty.is_empty() || selector.is_empty() {
+        bail!("Expected <type> <rank> or <type>/<name>, got: {}", entry);
+    }
+
+    let items = fetch_hub_items(url.as_deref(), &[ty.to_string()])
+        .context("Failed to fetch the hub file")?;
+    if items.is_empty() {
+        bail!("No hub entries of type {}", ty);
+    }
+
+    // Ranks only make sense in star order, so only fetch stars when asked for one
+    let (hub_ty, link_url) = if let Ok(rank) = selector.parse::<usize>() {
+        let ranked = rank_hub_items(items);
+        let count = ranked.entries.len();
+        let found = rank
+            .checked_sub(1)
+            .and_then(|i| ranked.entries.into_iter().nth(i))
+            .with_context(|| format!("Rank {} out of range; {} has {} entries", rank, ty, count))?;
+        (found.ty, found.link)
+    } else {
+        items
+            .into_iter()
+            .find(|(_, l)| derive_repo_name(l).eq_ignore_ascii_case(selector))
+            .ok_or_else(|| {
+                Failure::new(
+                    ErrorKind::NotFound,
+                    format!("No {} entry named {} in the hub", ty, selector),
+                )
+            })?
+    };
+
+    let installed = cmd_install(
+        &link_url,
+        None,
+        CloneStrategy::default(),
+        TrustPolicy::default(),
+        false,
+    )?;
+    if link {
+        cmd_link(&installed.key, None, &hub_ty.to_lowercase(), true)?;
+    }
+    Ok(())
+}
+
 fn start_spinner(message: &str) -> Arc<AtomicBool> {
     let stop = Arc::new(AtomicBool::new(false));
     let stop_clone = Arc::clone(&stop);
@@ -0,0 +1,239 @@
+//! Per-repo metadata kept next to the store in `<store>/.dothub/meta.yml`.
+
+use anyhow::{Context, Result};
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+/// Serializes load-modify-save cycles when repos are installed concurrently.
+static META_LOCK: Mutex<()> = Mutex::new(());
+
+/// Hidden directory inside the store holding dothub's own state.
+pub const STATE_DIR: &str = ".dothub";
+const META_FILE: &str = "meta.yml";
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct StoreMeta {
+    #[serde(default)]
+    pub repos: BTreeMap<String, RepoMeta>,
+}
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct RepoMeta {
+    /// Short name for repos stored under an owner-qualified key
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub alias: Option<String>,
+    /// What kind of source `url` points at
+    #[serde(default, skip_serializing_if = "SourceKind::is_git")]
+    pub source: SourceKind,
+    /// URL or path the repo was installed from
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+    /// Branch, tag or commit the repo is locked to
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pin: Option<String>,
+    /// How the repo was cloned
+    #[serde(default, skip_serializing_if = "CloneStrategy::is_full")]
+    pub clone: CloneStrategy,
+    /// Subpaths checked out in a sparse clone
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub sparse_paths: Vec<String>,
+    /// Which commits may be checked out
+    #[serde(default, skip_serializing_if = "TrustPolicy::is_open")]
+    pub trust: TrustPolicy,
+    /// Local branch with personal changes, replayed onto upstream by update
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fork: Option<Fork>,
+    /// What update does when local modifications are in the way
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub on_local_changes: Option<LocalChanges>,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SourceKind {
+    /// Cloned with git
+    #[default]
+    Git,
+    /// Copied from a local directory
+    Copy,
+    /// Symlinked to a local directory
+    Symlink,
+    /// Extracted from a local or remote .tar.gz/.zip
+    Archive,
+}
+
+impl SourceKind {
+    pub fn is_git(&self) -> bool {
+        *self == SourceKind::Git
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            SourceKind::Git => "git",
+            SourceKind::Copy => "copy",
+            SourceKind::Symlink => "symlink",
+            SourceKind::Archive => "archive",
+        }
+    }
+}
+
+/// Clone options for large repos, remembered so later git calls stay consistent.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CloneStrategy {
+    /// Shallow clone with this many commits of history
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub depth: Option<u32>,
+    /// Partial clone that fetches file contents on demand
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub blobless: bool,
+    /// Only check out the subpaths that are linked
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub sparse: bool,
+}
+
+impl CloneStrategy {
+    pub fn is_full(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// Extra arguments for `git clone`.
+    pub fn clone_args(&self) -> Vec<String> {
+        let mut args = Vec::new();
+        if let Some(depth) = self.depth {
+            args.push(format!("--depth={}", depth));
+        }
+        if self.blobless {
+            args.push("--filter=blob:none".to_string());
+        }
+        if self.sparse {
+            args.push("--sparse".to_string());
+        }
+        args
+    }
+}
+
+/// A local branch for personal tweaks to an installed config.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Fork {
+    pub branch: String,
+    #[serde(default)]
+    pub integrate: Integrate,
+}
+
+/// How `dothub update` brings upstream changes into a fork branch.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Integrate {
+    /// Replay local commits on top of upstream
+    #[default]
+    Rebase,
+    /// Merge upstream into the local branch
+    Merge,
+}
+
+impl Integrate {
+    /// The git subcommand that does it.
+    pub fn command(self) -> &'static str {
+        match self {
+            Integrate::Rebase => "rebase",
+            Integrate::Merge => "merge",
+        }
+    }
+}
+
+/// What `dothub update` does with a repo whose worktree has uncommitted
+/// changes, or local commits upstream does not have, when upstream moved on.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum LocalChanges {
+    /// Try to fast-forward anyway and report the failure
+    #[default]
+    Refuse,
+    /// Stash uncommitted changes, update, then restore them
+    Autostash,
+    /// Rebase local commits and stashed changes onto upstream
+    Rebase,
+    /// Save local changes to a patch file and reset to upstream
+    ResetToUpstream,
+    /// Leave the repo alone
+    Skip,
+}
+
+impl LocalChanges {
+    pub fn name(self) -> &'static str {
+        match self {
+            LocalChanges::Refuse => "refuse",
+            LocalChanges::Autostash => "autostash",
+            LocalChanges::Rebase => "rebase",
+            LocalChanges::ResetToUpstream => "reset-to-upstream",
+            LocalChanges::Skip => "skip",
+        }
+    }
+}
+
+/// Signature requirements for commits that install, update and pin check out.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TrustPolicy {
+    /// Refuse commits without a good signature from a key gpg or ssh trusts
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub require_signed: bool,
+    /// Only accept signatures from these key ids or fingerprints
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub trusted_keys: Vec<String>,
+}
+
+impl TrustPolicy {
+    pub fn is_open(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// Whether commits must be signed at all; listing keys implies it.
+    pub fn enforced(&self) -> bool {
+        self.require_signed || !self.trusted_keys.is_empty()
+    }
+}
+
+pub fn state_dir(store: &Path) -> PathBuf {
+    store.join(STATE_DIR)
+}
+
+impl StoreMeta {
+    pub fn load(store: &Path) -> Result<Self> {
+        let path = state_dir(store).join(META_FILE);
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let text =
+            fs::read_to_string(&path).with_context(|| format!("Reading {}", path.display()))?;
+        serde_yaml::from_str(&text).with_context(|| format!("Parsing {}", path.display()))
+    }
+
+    pub fn save(&self, store: &Path) -> Result<()> {
+        let dir = state_dir(store);
+        fs::create_dir_all(&dir).with_context(|| format!("Failed creating {}", dir.display()))?;
+        let path = dir.join(META_FILE);
+        let text = serde_yaml::to_string(self).context("Serializing store metadata")?;
+        fs::write(&path, text).with_context(|| format!("Writing {}", path.display()))
+    }
+
+    /// Load, modify and save the metadata without racing other threads.
+    pub fn update<T>(store: &Path, f: impl FnOnce(&mut StoreMeta) -> T) -> Result<T> {
+        let _guard = META_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+        let mut meta = Self::load(store)?;
+        let out = f(&mut meta);
+        meta.save(store)?;
+        Ok(out)
+    }
+
+    pub fn repo(&self, name: &str) -> Option<&RepoMeta> {
+        self.repos.get(name)
+    }
+
+    pub fn repo_mut(&mut self, name: &str) -> &mut RepoMeta {
+        self.repos.entry(name.to_string()).or_default()
+    }
+}
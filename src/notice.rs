@@ -0,0 +1,173 @@
+//! Passive "repos have updates" notice. At most once per
+//! `settings.update_check_hours`, any command asks the remotes for their
+//! branch tips while it runs and mentions outdated repos afterwards.
+
+use crate::config::Config;
+use crate::{git, load_store};
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io::IsTerminal;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+/// Set to anything to skip the check for one run
+const DISABLE_ENV: &str = "DOTHUB_NO_UPDATE_CHECK";
+const CACHE_FILE: &str = "update-check.yml";
+/// How long a finished command waits for a slow remote before falling back
+/// to what the previous check found
+const GRACE: Duration = Duration::from_millis(1500);
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct LastCheck {
+    /// Unix seconds
+    checked: u64,
+    /// Repos whose upstream had commits HEAD lacks
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    outdated: Vec<String>,
+}
+
+fn cache_path() -> Option<PathBuf> {
+    dirs::cache_dir().map(|d| d.join("dothub").join(CACHE_FILE))
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+impl LastCheck {
+    fn load(path: &Path) -> Self {
+        fs::read_to_string(path)
+            .ok()
+            .and_then(|text| serde_yaml::from_str(&text).ok())
+            .unwrap_or_default()
+    }
+
+    fn save(&self, path: &Path) -> Result<()> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).with_context(|| format!("Creating {}", parent.display()))?;
+        }
+        fs::write(path, serde_yaml::to_string(self)?)
+            .with_context(|| format!("Writing {}", path.display()))
+    }
+}
+
+/// A check running alongside the command.
+pub struct Pending {
+    started: Instant,
+    /// What the last check that finished found
+    previous: Vec<String>,
+    result: Receiver<Vec<String>>,
+}
+
+/// Start a check if one is due. Never fails; a broken check just does not
+/// produce a notice.
+pub fn start() -> Option<Pending> {
+    if std::env::var_os(DISABLE_ENV).is_some() || !std::io::stderr().is_terminal() {
+        return None;
+    }
+    let hours = Config::load_or_default().ok()?.settings.update_check_hours;
+    if hours == 0 || git::ensure_available().is_err() {
+        return None;
+    }
+    let cache = cache_path()?;
+    let mut last = LastCheck::load(&cache);
+    let checked = now();
+    if checked.saturating_sub(last.checked) < hours * 3600 {
+        return None;
+    }
+    // Claim the slot up front so an abandoned check is not retried on every command
+    last.checked = checked;
+    last.save(&cache).ok()?;
+
+    let (tx, rx) = mpsc::channel();
+    thread::spawn(move || {
+        if let Ok(outdated) = check() {
+            // Saved here so a result the command did not wait for still
+            // shows up next time
+            let _ = LastCheck {
+                checked: now(),
+                outdated: outdated.clone(),
+            }
+            .save(&cache);
+            let _ = tx.send(outdated);
+        }
+    });
+    Some(Pending {
+        started: Instant::now(),
+        previous: last.outdated,
+        result: rx,
+    })
+}
+
+impl Pending {
+    /// Wait briefly for the check and print the notice on stderr, from the
+    /// previous check when this one is too slow.
+    pub fn finish(self) {
+        let wait = GRACE.saturating_sub(self.started.elapsed());
+        let outdated = self.result.recv_timeout(wait).unwrap_or(self.previous);
+        match outdated.len() {
+            0 => {}
+            1 => eprintln!("\n1 repo has updates ({}); run dothub update", outdated[0]),
+            n => eprintln!(
+                "\n{} repos have updates ({}); run dothub update",
+                n,
+                outdated.join(", ")
+            ),
+        }
+    }
+}
+
+/// Ask every tracking repo's remote for its branch tip, all at once.
+fn check() -> Result<Vec<String>> {
+    let (meta, index) = load_store()?;
+    let handles: Vec<_> = index
+        .entries
+        .into_iter()
+        .filter(|e| {
+            let m = meta.repo(&e.key);
+            m.map(|m| m.source).unwrap_or_default().is_git()
+                && m.and_then(|m| m.pin.as_ref()).is_none()
+                && e.path.join(".git").exists()
+        })
+        .map(|e| thread::spawn(move || has_updates(&e.path).then_some(e.alias)))
+        .collect();
+    let mut outdated: Vec<String> = handles
+        .into_iter()
+        .filter_map(|h| h.join().ok().flatten())
+        .collect();
+    outdated.sort();
+    Ok(outdated)
+}
+
+/// Whether upstream has commits HEAD lacks: fetched but not pulled, or new on
+/// the remote. Nothing is fetched.
+fn has_updates(repo: &Path) -> bool {
+    let Ok(upstream) = git::output(repo, &["rev-parse", "--abbrev-ref", "@{upstream}"]) else {
+        return false;
+    };
+    let behind = git::output(repo, &["rev-list", "--count", "HEAD..@{upstream}"]);
+    if behind.is_ok_and(|n| n != "0") {
+        return true;
+    }
+    let Some((remote, branch)) = upstream.split_once('/') else {
+        return false;
+    };
+    let branch_ref = format!("refs/heads/{}", branch);
+    // Runs behind the user's command, so it must never prompt
+    let Ok(out) = git::output_unattended(repo, &["ls-remote", remote, &branch_ref]) else {
+        return false;
+    };
+    let Some(tip) = out.split_whitespace().next() else {
+        return false;
+    };
+    let Ok(tracked) = git::output(repo, &["rev-parse", "@{upstream}"]) else {
+        return false;
+    };
+    tip != tracked && !git::has_commit(repo, tip)
+}
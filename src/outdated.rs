@@ -0,0 +1,146 @@
+//! `dothub outdated`: fetch every repo and report what an update would bring
+//! in, without touching any worktree.
+
+use crate::meta::RepoMeta;
+use crate::store::StoreEntry;
+use crate::{backend, dothub_dir, ensure_store_dir, history, load_store, progress, styled_table};
+use anyhow::{Context, Result};
+
+/// Where a repo stands against its upstream after a fetch.
+enum Standing {
+    Tracking {
+        ahead: usize,
+        behind: usize,
+        /// Committer time of the upstream tip
+        upstream_time: i64,
+        /// Why `dothub update` could not fast-forward right now
+        blocked: Option<&'static str>,
+    },
+    Pinned(String),
+    NotGit,
+    Detached,
+}
+
+fn check_repo(
+    entry: &StoreEntry,
+    repo_meta: Option<&RepoMeta>,
+    status: &progress::Status,
+) -> Result<Standing> {
+    let path = &entry.path;
+    if !repo_meta.map(|m| m.source).unwrap_or_default().is_git() || !path.join(".git").exists() {
+        return Ok(Standing::NotGit);
+    }
+    let git = match repo_meta {
+        Some(m) => backend::for_strategy(&m.clone),
+        None => backend::current(),
+    };
+    status.set("fetching");
+    git.fetch(path)
+        .with_context(|| format!("Fetching with the {} backend", git.name()))?;
+    if let Some(pin) = repo_meta.and_then(|m| m.pin.as_deref()) {
+        return Ok(Standing::Pinned(pin.to_string()));
+    }
+    let state = git.status(path)?;
+    if state.branch.is_none() {
+        return Ok(Standing::Detached);
+    }
+
+    status.set("comparing");
+    let (ahead, behind) = git.ahead_behind(path)?;
+    let upstream_time = git.commit_time(path, &git.upstream_commit(path)?)?;
+    let forked = repo_meta.is_some_and(|m| m.fork.is_some());
+    let blocked = if behind == 0 {
+        None
+    } else if state.dirty {
+        Some("local modifications")
+    } else if ahead > 0 && !forked {
+        Some("local commits")
+    } else {
+        None
+    };
+    Ok(Standing::Tracking {
+        ahead,
+        behind,
+        upstream_time,
+        blocked,
+    })
+}
+
+fn describe(result: &Result<Standing>) -> String {
+    match result {
+        Ok(Standing::Tracking { behind: 0, .. }) => "up to date".to_string(),
+        Ok(Standing::Tracking { behind, .. }) => format!("{} new commits", behind),
+        Ok(Standing::Pinned(pin)) => format!("pinned at {}", pin),
+        Ok(Standing::NotGit) => "not a git repository".to_string(),
+        Ok(Standing::Detached) => "HEAD is detached".to_string(),
+        Err(e) => format!("failed: {:#}", e),
+    }
+}
+
+pub fn cmd_outdated(names: &[String], jobs: usize) -> Result<()> {
+    ensure_store_dir()?;
+    backend::current().ensure_available()?;
+    let root = dothub_dir();
+    let (meta, index) = load_store()?;
+    if index.entries.is_empty() {
+        println!("No repositories installed in {}.", root.display());
+        return Ok(());
+    }
+    let selected = if names.is_empty() {
+        index.entries
+    } else {
+        index.select(names)?
+    };
+    let items: Vec<(String, StoreEntry)> =
+        selected.into_iter().map(|e| (e.alias.clone(), e)).collect();
+
+    let results = progress::run(
+        jobs,
+        &items,
+        |entry, status| check_repo(entry, meta.repo(&entry.key), status),
+        describe,
+    );
+
+    let mut table = styled_table();
+    table.set_header(["Repo", "Behind", "Ahead", "Upstream commit", "Fast-forward"]);
+    let mut outdated = 0usize;
+    for ((name, _), result) in items.iter().zip(&results) {
+        let row = match result {
+            Ok(Standing::Tracking {
+                ahead,
+                behind,
+                upstream_time,
+                blocked,
+            }) => {
+                if *behind > 0 {
+                    outdated += 1;
+                }
+                let ff = match (behind, blocked) {
+                    (0, _) => "-".to_string(),
+                    (_, Some(why)) => format!("blocked by {}", why),
+                    _ => "ok".to_string(),
+                };
+                [
+                    behind.to_string(),
+                    ahead.to_string(),
+                    history::format_time((*upstream_time).max(0) as u64),
+                    ff,
+                ]
+            }
+            other => [String::new(), String::new(), String::new(), describe(other)],
+        };
+        let mut cells = vec![name.clone()];
+        cells.extend(row);
+        table.add_row(cells);
+    }
+    println!("{}", table);
+    match outdated {
+        0 => println!("Everything is up to date."),
+        1 => println!("1 repository has updates; run dothub update to pull it."),
+        n => println!(
+            "{} repositories have updates; run dothub update to pull them.",
+            n
+        ),
+    }
+    Ok(())
+}
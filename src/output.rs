@@ -0,0 +1,169 @@
+//! `--format json|yaml`: machine-readable output for scripts, including
+//! errors with a kind and the exit code they end the process with.
+
+use anyhow::Result;
+use clap::ValueEnum;
+use serde::Serialize;
+use std::fmt;
+use std::process::ExitCode;
+use std::sync::OnceLock;
+
+static FORMAT: OnceLock<Format> = OnceLock::new();
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Format {
+    /// Tables and messages for people
+    #[default]
+    Text,
+    Json,
+    Yaml,
+}
+
+pub fn set(format: Format) {
+    let _ = FORMAT.set(format);
+}
+
+pub fn format() -> Format {
+    FORMAT.get().copied().unwrap_or_default()
+}
+
+/// Whether commands should print a document instead of their usual text.
+pub fn structured() -> bool {
+    format() != Format::Text
+}
+
+/// Print `value` as the command's single JSON or YAML document.
+pub fn emit<T: Serialize>(value: &T) -> Result<()> {
+    match format() {
+        Format::Json => println!("{}", serde_json::to_string_pretty(value)?),
+        Format::Yaml => print!("{}", serde_yaml::to_string(value)?),
+        Format::Text => {}
+    }
+    Ok(())
+}
+
+/// Broad class of a failure; each maps to its own exit code.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorKind {
+    /// Anything not classified below
+    Other,
+    /// Bad arguments
+    InvalidInput,
+    /// A repo, hub entry or file that does not exist
+    NotFound,
+    /// A git command failed
+    Git,
+    /// The hub or a remote could not be reached
+    Network,
+    /// A commit was refused by the repo's trust policy
+    Trust,
+    /// Reading or writing the filesystem failed
+    Io,
+    /// dothub.yml, dothub.lock or store metadata could not be parsed
+    Config,
+}
+
+impl ErrorKind {
+    pub fn exit_code(self) -> u8 {
+        match self {
+            ErrorKind::Other => 1,
+            ErrorKind::InvalidInput => 2,
+            ErrorKind::NotFound => 3,
+            ErrorKind::Git => 4,
+            ErrorKind::Network => 5,
+            ErrorKind::Trust => 6,
+            ErrorKind::Io => 7,
+            ErrorKind::Config => 8,
+        }
+    }
+}
+
+/// An error that knows its kind, for `bail!(Failure::new(..))` or as the
+/// outermost `.context(..)`.
+#[derive(Debug)]
+pub struct Failure {
+    pub kind: ErrorKind,
+    message: String,
+}
+
+impl Failure {
+    pub fn new(kind: ErrorKind, message: impl Into<String>) -> Self {
+        Failure {
+            kind,
+            message: message.into(),
+        }
+    }
+}
+
+impl fmt::Display for Failure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl std::error::Error for Failure {}
+
+/// The kind of the outermost classified error in the chain, falling back to
+/// what the underlying library error says.
+pub fn kind_of(err: &anyhow::Error) -> ErrorKind {
+    // Context is only visible to `downcast_ref` on the error itself
+    if let Some(failure) = err.downcast_ref::<Failure>() {
+        return failure.kind;
+    }
+    if let Some(failure) = err.chain().find_map(|c| c.downcast_ref::<Failure>()) {
+        return failure.kind;
+    }
+    for cause in err.chain() {
+        if cause.is::<reqwest::Error>() {
+            return ErrorKind::Network;
+        }
+        if cause.is::<serde_yaml::Error>() || cause.is::<serde_json::Error>() {
+            return ErrorKind::Config;
+        }
+        if let Some(io) = cause.downcast_ref::<std::io::Error>() {
+            return match io.kind() {
+                std::io::ErrorKind::NotFound => ErrorKind::NotFound,
+                _ => ErrorKind::Io,
+            };
+        }
+    }
+    ErrorKind::Other
+}
+
+#[derive(Serialize)]
+struct ErrorDocument {
+    error: ErrorReport,
+}
+
+#[derive(Serialize)]
+struct ErrorReport {
+    kind: ErrorKind,
+    message: String,
+    /// Context down to the root cause
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    causes: Vec<String>,
+    exit_code: u8,
+}
+
+/// Print `err` for the chosen format and return the matching exit code.
+pub fn report(err: &anyhow::Error) -> ExitCode {
+    let kind = kind_of(err);
+    let code = kind.exit_code();
+    if !structured() {
+        eprintln!("Error: {:?}", err);
+        return ExitCode::from(code);
+    }
+    let doc = ErrorDocument {
+        error: ErrorReport {
+            kind,
+            message: err.to_string(),
+            causes: err.chain().skip(1).map(|c| c.to_string()).collect(),
+            exit_code: code,
+        },
+    };
+    if emit(&doc).is_err() {
+        eprintln!("Error: {:?}", err);
+    }
+    ExitCode::from(code)
+}
@@ -0,0 +1,206 @@
+//! Running per-repo jobs concurrently with a live status line for each repo.
+
+use std::any::Any;
+use std::io::{IsTerminal, Write};
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::thread;
+use std::time::Duration;
+
+const FRAMES: [&str; 4] = ["-", "\\", "|", "/"];
+
+/// Number of [`run`] calls currently working on more than one job at a time.
+static CONCURRENT: AtomicUsize = AtomicUsize::new(0);
+
+/// Whether jobs are running side by side, so nothing can stop to ask the user.
+pub fn concurrent() -> bool {
+    CONCURRENT.load(Ordering::SeqCst) > 0
+}
+
+/// Default number of concurrent jobs.
+pub fn default_jobs() -> usize {
+    thread::available_parallelism()
+        .map(|n| n.get())
+        .unwrap_or(4)
+        .min(8)
+}
+
+struct Line {
+    label: String,
+    state: String,
+    done: bool,
+}
+
+/// Handle a job uses to report what it is doing.
+pub struct Status<'a> {
+    board: &'a Board,
+    slot: usize,
+}
+
+impl Status<'_> {
+    pub fn set(&self, state: &str) {
+        self.board.update(self.slot, state, false);
+    }
+}
+
+struct Board {
+    lines: Mutex<Vec<Line>>,
+    tty: bool,
+}
+
+impl Board {
+    fn update(&self, slot: usize, state: &str, done: bool) {
+        let mut lines = self.lines.lock().unwrap_or_else(|e| e.into_inner());
+        let line = &mut lines[slot];
+        line.state = state.to_string();
+        line.done = done;
+        // Without a terminal to redraw, only report finished jobs
+        if done && !self.tty {
+            eprintln!("{}: {}", line.label, line.state);
+        }
+    }
+
+    fn draw(&self, frame: usize, first: bool) {
+        let lines = self.lines.lock().unwrap_or_else(|e| e.into_inner());
+        let mut err = std::io::stderr().lock();
+        if !first {
+            let _ = write!(err, "\x1b[{}A", lines.len());
+        }
+        for line in lines.iter() {
+            let mark = if line.done {
+                " "
+            } else {
+                FRAMES[frame % FRAMES.len()]
+            };
+            let _ = writeln!(err, "\x1b[2K{} {}: {}", mark, line.label, line.state);
+        }
+        let _ = err.flush();
+    }
+
+    fn all_done(&self) -> bool {
+        let lines = self.lines.lock().unwrap_or_else(|e| e.into_inner());
+        lines.iter().all(|l| l.done)
+    }
+}
+
+/// Run `work` for every item on up to `jobs` threads, showing one status line
+/// per item. `finish` turns each result into the line's final state. Results
+/// come back in the order of `items`.
+pub fn run<T, R, W, F>(jobs: usize, items: &[(String, T)], work: W, finish: F) -> Vec<R>
+where
+    T: Sync,
+    R: Send,
+    W: Fn(&T, &Status) -> R + Sync,
+    F: Fn(&R) -> String + Sync,
+{
+    if items.is_empty() {
+        return Vec::new();
+    }
+    let board = Board {
+        lines: Mutex::new(
+            items
+                .iter()
+                .map(|(label, _)| Line {
+                    label: label.clone(),
+                    state: "waiting".to_string(),
+                    done: false,
+                })
+                .collect(),
+        ),
+        tty: std::io::stderr().is_terminal(),
+    };
+    let next = AtomicUsize::new(0);
+    let results: Mutex<Vec<Option<R>>> = Mutex::new(items.iter().map(|_| None).collect());
+    // The first job to panic; raised again once the board has stopped
+    let panicked: Mutex<Option<Box<dyn Any + Send>>> = Mutex::new(None);
+
+    let workers = jobs.clamp(1, items.len());
+    if workers > 1 {
+        CONCURRENT.fetch_add(1, Ordering::SeqCst);
+    }
+    thread::scope(|scope| {
+        for _ in 0..workers {
+            scope.spawn(|| loop {
+                let slot = next.fetch_add(1, Ordering::SeqCst);
+                let Some((_, item)) = items.get(slot) else {
+                    break;
+                };
+                let status = Status {
+                    board: &board,
+                    slot,
+                };
+                match panic::catch_unwind(AssertUnwindSafe(|| work(item, &status))) {
+                    Ok(result) => {
+                        board.update(slot, &finish(&result), true);
+                        results.lock().unwrap_or_else(|e| e.into_inner())[slot] = Some(result);
+                    }
+                    // Left unfinished, the line would keep the draw loop and
+                    // so the whole scope waiting forever
+                    Err(payload) => {
+                        board.update(slot, "failed: panicked", true);
+                        panicked
+                            .lock()
+                            .unwrap_or_else(|e| e.into_inner())
+                            .get_or_insert(payload);
+                    }
+                }
+            });
+        }
+        if board.tty {
+            scope.spawn(|| {
+                let mut frame = 0usize;
+                board.draw(frame, true);
+                loop {
+                    thread::sleep(Duration::from_millis(120));
+                    frame += 1;
+                    // Check before drawing so the final states always get drawn
+                    let done = board.all_done();
+                    board.draw(frame, false);
+                    if done {
+                        break;
+                    }
+                }
+            });
+        }
+    });
+    if workers > 1 {
+        CONCURRENT.fetch_sub(1, Ordering::SeqCst);
+    }
+    if let Some(payload) = panicked.into_inner().unwrap_or_else(|e| e.into_inner()) {
+        panic::resume_unwind(payload);
+    }
+
+    results
+        .into_inner()
+        .unwrap_or_else(|e| e.into_inner())
+        .into_iter()
+        .map(|r| r.expect("every job stores a result"))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn a_panicking_job_fails_the_run_instead_of_hanging() {
+        let items: Vec<(String, u32)> = (0..4).map(|i| (i.to_string(), i)).collect();
+        let ran = AtomicUsize::new(0);
+        let outcome = panic::catch_unwind(AssertUnwindSafe(|| {
+            run(
+                1,
+                &items,
+                |&i, _| {
+                    ran.fetch_add(1, Ordering::SeqCst);
+                    assert_ne!(i, 1, "job 1 fails");
+                    i
+                },
+                |i| i.to_string(),
+            )
+        }));
+        assert!(outcome.is_err());
+        // The worker moved on to the jobs after the one that panicked
+        assert_eq!(ran.load(Ordering::SeqCst), items.len());
+    }
+}
@@ -0,0 +1,288 @@
+//! `dothub schedule`: run `dothub update` unattended from a systemd user
+//! timer, or print a crontab line where systemd is not around.
+
+use crate::meta::{state_dir, LocalChanges};
+use crate::{dothub_dir, ensure_store_dir};
+use anyhow::{bail, Context, Result};
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+const UNIT: &str = "dothub-update";
+const LOG_FILE: &str = "update.log";
+
+/// How often the timer fires.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Every {
+    Hour,
+    Day,
+    Week,
+}
+
+impl Every {
+    fn on_calendar(self) -> &'static str {
+        match self {
+            Every::Hour => "hourly",
+            Every::Day => "daily",
+            Every::Week => "weekly",
+        }
+    }
+
+    fn cron(self) -> &'static str {
+        match self {
+            Every::Hour => "0 * * * *",
+            Every::Day => "0 9 * * *",
+            Every::Week => "0 9 * * 1",
+        }
+    }
+}
+
+fn unit_dir() -> Result<PathBuf> {
+    let config = dirs::config_dir().context("Unable to determine the config directory")?;
+    Ok(config.join("systemd").join("user"))
+}
+
+/// Where scheduled runs append their output.
+fn log_path() -> Result<PathBuf> {
+    ensure_store_dir()?;
+    let dir = state_dir(&dothub_dir());
+    fs::create_dir_all(&dir).with_context(|| format!("Creating {}", dir.display()))?;
+    // Unit files and crontab need an absolute path
+    Ok(dir.canonicalize()?.join(LOG_FILE))
+}
+
+/// The command line a scheduled run executes.
+fn update_command(strategy: Option<LocalChanges>) -> Result<Vec<String>> {
+    let exe = env::current_exe().context("Locating the dothub executable")?;
+    let mut args = vec![exe.display().to_string(), "update".to_string()];
+    if let Some(s) = strategy {
+        args.push("--on-local-changes".to_string());
+        args.push(s.name().to_string());
+    }
+    Ok(args)
+}
+
+/// Whether `word` can go into a crontab line or unit file unquoted.
+fn is_plain(word: &str) -> bool {
+    !word.is_empty()
+        && word
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "/._-+:=@".contains(c))
+}
+
+/// Quote a word for the shell cron runs the line with.
+fn shell_quote(word: &str) -> String {
+    if is_plain(word) {
+        return word.to_string();
+    }
+    format!("'{}'", word.replace('\'', r"'\''"))
+}
+
+/// Quote a word for a systemd unit file, which only knows double quotes with
+/// C-style escapes and reads `%` as the start of a specifier.
+fn unit_quote(word: &str) -> String {
+    let word = word.replace('%', "%%");
+    if is_plain(&word) {
+        return word;
+    }
+    format!("\"{}\"", word.replace('\\', r"\\").replace('"', r#"\""#))
+}
+
+fn systemctl(args: &[&str]) -> Result<String> {
+    let out = Command::new("systemctl")
+        .arg("--user")
+        .args(args)
+        .output()
+        .context("Running systemctl")?;
+    let stdout = String::from_utf8_lossy(&out.stdout).trim().to_string();
+    if !out.status.success() {
+        let stderr = String::from_utf8_lossy(&out.stderr).trim().to_string();
+        bail!(
+            "systemctl --user {} failed: {}",
+            args.join(" "),
+            if stderr.is_empty() { &stdout } else { &stderr }
+        );
+    }
+    Ok(stdout)
+}
+
+/// Whether a systemd user manager is there to talk to.
+fn has_systemd() -> bool {
+    which::which("systemctl").is_ok() && systemctl(&["show-environment"]).is_ok()
+}
+
+/// `DOTHUB_DIR` as an absolute path, when it is set for this run.
+fn store_override() -> Option<String> {
+    env::var_os("DOTHUB_DIR")?;
+    let store = dothub_dir();
+    Some(store.canonicalize().unwrap_or(store).display().to_string())
+}
+
+fn cron_line(every: Every, command: &[String], log: &Path) -> String {
+    let mut words = Vec::new();
+    if let Some(store) = store_override() {
+        words.push(format!("DOTHUB_DIR={}", shell_quote(&store)));
+    }
+    words.extend(command.iter().map(|w| shell_quote(w)));
+    let command = format!(
+        "{} >> {} 2>&1",
+        words.join(" "),
+        shell_quote(&log.display().to_string())
+    );
+    // cron turns a bare % into a newline, even inside quotes
+    format!("{} {}", every.cron(), command.replace('%', r"\%"))
+}
+
+pub fn cmd_enable(every: Every, strategy: Option<LocalChanges>, cron: bool) -> Result<()> {
+    let command = update_command(strategy)?;
+    let log = log_path()?;
+    if cron || !has_systemd() {
+        if !cron {
+            println!("No systemd user session found; add this line with crontab -e instead:");
+        }
+        println!("{}", cron_line(every, &command, &log));
+        return Ok(());
+    }
+
+    // StandardOutput=append: takes the rest of the line as the path, unquoted
+    let log_file = log.display().to_string();
+    if log_file.contains(|c: char| c.is_whitespace() || "\"'\\".contains(c)) {
+        bail!(
+            "systemd cannot append to {}, which has spaces or quotes in its path; \
+             use --cron or a DOTHUB_DIR without them",
+            log_file
+        );
+    }
+    let log_file = log_file.replace('%', "%%");
+
+    let dir = unit_dir()?;
+    fs::create_dir_all(&dir).with_context(|| format!("Creating {}", dir.display()))?;
+    let mut environment = String::new();
+    if let Some(store) = store_override() {
+        environment = format!(
+            "Environment={}\n",
+            unit_quote(&format!("DOTHUB_DIR={}", store))
+        );
+    }
+    // ExecStart= also expands $VARIABLES, so a literal $ is doubled
+    let exec: Vec<String> = command
+        .iter()
+        .map(|w| unit_quote(&w.replace('$', "$$")))
+        .collect();
+    let service = format!(
+        "[Unit]\n\
+         Description=Update dothub repositories\n\
+         Wants=network-online.target\n\
+         After=network-online.target\n\
+         \n\
+         [Service]\n\
+         Type=oneshot\n\
+         {}ExecStart={}\n\
+         StandardOutput=append:{}\n\
+         StandardError=append:{}\n",
+        environment,
+        exec.join(" "),
+        log_file,
+        log_file
+    );
+    let timer = format!(
+        "[Unit]\n\
+         Description=Run dothub update {}\n\
+         \n\
+         [Timer]\n\
+         OnCalendar={}\n\
+         Persistent=true\n\
+         RandomizedDelaySec=10min\n\
+         \n\
+         [Install]\n\
+         WantedBy=timers.target\n",
+        every.on_calendar(),
+        every.on_calendar()
+    );
+    for (ext, text) in [("service", service), ("timer", timer)] {
+        let path = dir.join(format!("{}.{}", UNIT, ext));
+        fs::write(&path, text).with_context(|| format!("Writing {}", path.display()))?;
+    }
+    systemctl(&["daemon-reload"])?;
+    systemctl(&["enable", "--now", &format!("{}.timer", UNIT)])?;
+
+    println!(
+        "Scheduled dothub update {} with {}.timer; output goes to {}",
+        every.on_calendar(),
+        UNIT,
+        log.display()
+    );
+    Ok(())
+}
+
+pub fn cmd_disable() -> Result<()> {
+    let dir = unit_dir()?;
+    let timer = dir.join(format!("{}.timer", UNIT));
+    let service = dir.join(format!("{}.service", UNIT));
+    if !timer.exists() && !service.exists() {
+        println!("No dothub update schedule is installed.");
+        println!("If you added a crontab line, remove it with crontab -e.");
+        return Ok(());
+    }
+    if has_systemd() {
+        if let Err(e) = systemctl(&["disable", "--now", &format!("{}.timer", UNIT)]) {
+            eprintln!("Warning: {:#}", e);
+        }
+    }
+    for path in [&timer, &service] {
+        if path.exists() {
+            fs::remove_file(path).with_context(|| format!("Removing {}", path.display()))?;
+        }
+    }
+    if has_systemd() {
+        systemctl(&["daemon-reload"])?;
+    }
+    println!("Removed the dothub update schedule.");
+    Ok(())
+}
+
+pub fn cmd_status() -> Result<()> {
+    let timer = unit_dir()?.join(format!("{}.timer", UNIT));
+    if !timer.exists() {
+        println!("No systemd schedule; run dothub schedule enable to add one.");
+    } else if !has_systemd() {
+        println!(
+            "{} exists, but no systemd user session is running",
+            timer.display()
+        );
+    } else {
+        let unit = format!("{}.timer", UNIT);
+        let enabled = systemctl(&["is-enabled", &unit]).unwrap_or_else(|e| format!("{:#}", e));
+        let next = systemctl(&[
+            "show",
+            &unit,
+            "--property=NextElapseUSecRealtime",
+            "--value",
+        ])
+        .unwrap_or_default();
+        let last = systemctl(&["show", &unit, "--property=LastTriggerUSec", "--value"])
+            .unwrap_or_default();
+        println!("{}: {}", unit, enabled);
+        if !next.is_empty() {
+            println!("Next run: {}", next);
+        }
+        if !last.is_empty() && last != "n/a" {
+            println!("Last run: {}", last);
+        }
+    }
+
+    let log = state_dir(&dothub_dir()).join(LOG_FILE);
+    match fs::read_to_string(&log) {
+        Ok(text) => {
+            let lines: Vec<&str> = text.lines().collect();
+            let tail = &lines[lines.len().saturating_sub(5)..];
+            println!("Last output ({}):", log.display());
+            for line in tail {
+                println!("  {}", line);
+            }
+        }
+        Err(_) => println!("No scheduled run has written to {} yet.", log.display()),
+    }
+    Ok(())
+}
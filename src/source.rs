@@ -0,0 +1,199 @@
+//! Installing and refreshing repos that do not come from git: local
+//! directories and `.tar.gz`/`.zip` archives, either on disk or over HTTP.
+
+use crate::meta::{self, SourceKind};
+use crate::store;
+use anyhow::{bail, Context, Result};
+use std::fs;
+use std::io::{Cursor, Read};
+use std::path::{Path, PathBuf};
+
+const ARCHIVE_EXTENSIONS: [&str; 4] = [".tar.gz", ".tgz", ".zip", ".tar"];
+
+/// The archive extension `spec` ends with, if any.
+pub fn archive_extension(spec: &str) -> Option<&'static str> {
+    let lower = spec.to_lowercase();
+    ARCHIVE_EXTENSIONS
+        .into_iter()
+        .find(|ext| lower.ends_with(ext))
+}
+
+fn is_remote(location: &str) -> bool {
+    location.starts_with("http://") || location.starts_with("https://")
+}
+
+/// Work out what kind of source an install argument refers to.
+pub fn classify(spec: &str, symlink: bool) -> SourceKind {
+    if archive_extension(spec).is_some() && (is_remote(spec) || Path::new(spec).is_file()) {
+        SourceKind::Archive
+    } else if Path::new(spec).is_dir() {
+        if symlink {
+            SourceKind::Symlink
+        } else {
+            SourceKind::Copy
+        }
+    } else {
+        SourceKind::Git
+    }
+}
+
+/// Place the contents of a non-git source at `dest`, which must not exist yet.
+pub fn install(kind: SourceKind, location: &str, store: &Path, dest: &Path) -> Result<()> {
+    match kind {
+        SourceKind::Git => bail!("git sources are cloned, not installed from {}", location),
+        SourceKind::Symlink => symlink_dir(Path::new(location), dest),
+        SourceKind::Copy => copy_dir(Path::new(location), dest),
+        SourceKind::Archive => {
+            let staging = staging_dir(store, dest)?;
+            let result = extract_into(location, &staging).and_then(|root| {
+                fs::rename(&root, dest)
+                    .with_context(|| format!("Moving {} to {}", root.display(), dest.display()))
+            });
+            let _ = fs::remove_dir_all(&staging);
+            result
+        }
+    }
+}
+
+/// Replace `dest` with a fresh copy of its source. Returns false for sources
+/// that are always current, such as symlinked directories.
+pub fn refresh(kind: SourceKind, location: &str, store: &Path, dest: &Path) -> Result<bool> {
+    match kind {
+        SourceKind::Git | SourceKind::Symlink => Ok(false),
+        SourceKind::Copy | SourceKind::Archive => {
+            if kind == SourceKind::Copy && !Path::new(location).is_dir() {
+                bail!("Source directory no longer exists: {}", location);
+            }
+            // Build the new copy first so a failure leaves the old one untouched
+            let fresh = meta::state_dir(store).join("refresh").join(dir_name(dest));
+            if fresh.exists() {
+                fs::remove_dir_all(&fresh)
+                    .with_context(|| format!("Removing {}", fresh.display()))?;
+            }
+            if let Some(parent) = fresh.parent() {
+                fs::create_dir_all(parent)
+                    .with_context(|| format!("Failed creating {}", parent.display()))?;
+            }
+            install(kind, location, store, &fresh)?;
+            store::ensure_inside(store, dest)?;
+            fs::remove_dir_all(dest).with_context(|| format!("Removing {}", dest.display()))?;
+            fs::rename(&fresh, dest)
+                .with_context(|| format!("Moving {} to {}", fresh.display(), dest.display()))?;
+            Ok(true)
+        }
+    }
+}
+
+fn dir_name(path: &Path) -> String {
+    path.file_name()
+        .map(|s| s.to_string_lossy().to_string())
+        .unwrap_or_default()
+}
+
+fn staging_dir(store: &Path, dest: &Path) -> Result<PathBuf> {
+    let dir = meta::state_dir(store).join("staging").join(dir_name(dest));
+    if dir.exists() {
+        fs::remove_dir_all(&dir).with_context(|| format!("Removing {}", dir.display()))?;
+    }
+    fs::create_dir_all(&dir).with_context(|| format!("Failed creating {}", dir.display()))?;
+    Ok(dir)
+}
+
+fn read_archive(location: &str) -> Result<Vec<u8>> {
+    if is_remote(location) {
+        let client = reqwest::blocking::Client::builder()
+            .user_agent("dothub/0.1")
+            .build()
+            .context("building http client")?;
+        let resp = client
+            .get(location)
+            .send()
+            .with_context(|| format!("GET {}", location))?;
+        if !resp.status().is_success() {
+            bail!("HTTP {} for {}", resp.status(), location);
+        }
+        Ok(resp.bytes().context("reading response body")?.to_vec())
+    } else {
+        fs::read(location).with_context(|| format!("Reading {}", location))
+    }
+}
+
+/// Extract an archive into `staging` and return the directory holding its
+/// contents; archives wrapping everything in one top-level folder are unwrapped.
+fn extract_into(location: &str, staging: &Path) -> Result<PathBuf> {
+    let bytes = read_archive(location)?;
+    match archive_extension(location) {
+        Some(".zip") => {
+            let mut zip =
+                zip::ZipArchive::new(Cursor::new(bytes)).context("Reading zip archive")?;
+            zip.extract(staging).context("Extracting zip archive")?;
+        }
+        Some(".tar") => unpack_tar(&bytes[..], staging)?,
+        Some(_) => unpack_tar(flate2::read::GzDecoder::new(&bytes[..]), staging)?,
+        None => bail!("Unsupported archive type: {}", location),
+    }
+
+    let entries: Vec<PathBuf> = fs::read_dir(staging)?
+        .filter_map(|e| e.ok().map(|e| e.path()))
+        .collect();
+    match entries.as_slice() {
+        [only] if only.is_dir() => Ok(only.clone()),
+        [] => bail!("Archive is empty: {}", location),
+        _ => {
+            let root = staging.join(".root");
+            fs::create_dir(&root)?;
+            for entry in entries {
+                let name = entry.file_name().context("archive entry without a name")?;
+                fs::rename(&entry, root.join(name))?;
+            }
+            Ok(root)
+        }
+    }
+}
+
+fn unpack_tar<R: Read>(reader: R, dest: &Path) -> Result<()> {
+    tar::Archive::new(reader)
+        .unpack(dest)
+        .context("Extracting tar archive")
+}
+
+fn symlink_dir(source: &Path, dest: &Path) -> Result<()> {
+    #[cfg(unix)]
+    let result = std::os::unix::fs::symlink(source, dest);
+    #[cfg(windows)]
+    let result = std::os::windows::fs::symlink_dir(source, dest);
+    result.with_context(|| {
+        format!(
+            "Failed creating symlink {} -> {}",
+            dest.display(),
+            source.display()
+        )
+    })
+}
+
+/// Recursively copy a directory, recreating symlinks rather than following them.
+fn copy_dir(source: &Path, dest: &Path) -> Result<()> {
+    fs::create_dir_all(dest).with_context(|| format!("Failed creating {}", dest.display()))?;
+    for entry in fs::read_dir(source).with_context(|| format!("Reading {}", source.display()))? {
+        let entry = entry?;
+        let from = entry.path();
+        let to = dest.join(entry.file_name());
+        let file_type = entry.file_type()?;
+        if file_type.is_symlink() {
+            let link = fs::read_link(&from)?;
+            #[cfg(unix)]
+            std::os::unix::fs::symlink(&link, &to)
+                .with_context(|| format!("Copying symlink {}", from.display()))?;
+            #[cfg(windows)]
+            {
+                let _ = link;
+                fs::copy(&from, &to).with_context(|| format!("Copying {}", from.display()))?;
+            }
+        } else if file_type.is_dir() {
+            copy_dir(&from, &to)?;
+        } else {
+            fs::copy(&from, &to).with_context(|| format!("Copying {}", from.display()))?;
+        }
+    }
+    Ok(())
+}
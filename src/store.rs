@@ -0,0 +1,540 @@
+//! Locating repos in the store. Git repos live under an owner-qualified
+//! identity such as `github.com/alice/dotfiles` and are also reachable by a
+//! short alias; local sources and repos installed before identities existed
+//! sit directly under the store root with their alias as the key.
+
+use crate::meta::{StoreMeta, STATE_DIR};
+use crate::output::{ErrorKind, Failure};
+use anyhow::{bail, Context, Result};
+use std::collections::BTreeSet;
+use std::fmt;
+use std::fs;
+use std::ops::Deref;
+use std::path::{Component, Path, PathBuf};
+use std::str::FromStr;
+
+/// A repo name that is safe to join onto the store root: a full key such as
+/// `github.com/alice/dotfiles`, whose components are checked one by one, or a
+/// short alias, which is a single component.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct RepoName(String);
+
+impl RepoName {
+    /// A name to look a stored repo up by: its key or its alias, which may
+    /// start with a dot. A trailing `/` is dropped, as shells add one when
+    /// completing directories.
+    pub fn lookup(name: &str) -> Result<Self> {
+        let name = name.strip_suffix('/').unwrap_or(name);
+        check_relative("Repo name", name)?;
+        Ok(RepoName(name.to_string()))
+    }
+
+    /// A store key, the path of a repo below the store root.
+    pub fn key(name: &str) -> Result<Self> {
+        let RepoName(name) = RepoName::lookup(name)?;
+        // Hidden top-level directories hold dothub's own state and are never listed
+        if name.starts_with('.') {
+            bail!(invalid(
+                "Repo name",
+                &name,
+                "must not start with a dot; pass another name"
+            ));
+        }
+        Ok(RepoName(name))
+    }
+
+    /// A short name for a repo, which may not contain separators. It may start
+    /// with a dot (`.dotfiles`) as long as it is not used as a key.
+    pub fn alias(name: &str) -> Result<Self> {
+        check_relative("Alias", name)?;
+        if name.contains('/') {
+            bail!(invalid("Alias", name, "must not contain /"));
+        }
+        if name == STATE_DIR {
+            bail!(invalid("Alias", name, "is reserved for dothub's own state"));
+        }
+        Ok(RepoName(name.to_string()))
+    }
+}
+
+impl FromStr for RepoName {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        RepoName::lookup(s)
+    }
+}
+
+impl Deref for RepoName {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl AsRef<Path> for RepoName {
+    fn as_ref(&self) -> &Path {
+        Path::new(&self.0)
+    }
+}
+
+impl fmt::Display for RepoName {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+fn invalid(what: &str, name: &str, reason: &str) -> Failure {
+    Failure::new(
+        ErrorKind::InvalidInput,
+        format!("{} {:?} {}", what, name, reason),
+    )
+}
+
+/// Check that `name` is a relative path of plain components, so joining it
+/// onto a directory cannot leave that directory.
+pub fn check_relative(what: &str, name: &str) -> Result<()> {
+    if name.is_empty() {
+        bail!(invalid(what, name, "must not be empty"));
+    }
+    if name.starts_with('/') || Path::new(name).has_root() {
+        bail!(invalid(
+            what,
+            name,
+            "must be relative, not an absolute path"
+        ));
+    }
+    if name.contains(['\\', '\0']) {
+        bail!(invalid(what, name, "must not contain \\ or NUL"));
+    }
+    for component in name.split('/') {
+        match component {
+            "" => bail!(invalid(what, name, "must not contain an empty component")),
+            "." | ".." => bail!(invalid(what, name, "must not contain . or .. components")),
+            // Anything else that is not a plain name, such as a drive prefix on Windows
+            c if !matches!(
+                Path::new(c).components().collect::<Vec<_>>().as_slice(),
+                [Component::Normal(_)]
+            ) =>
+            {
+                bail!(invalid(what, name, "must only contain plain names"))
+            }
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+/// Fail unless `path` lies strictly inside `dir` once symlinks are resolved.
+/// `path` itself may be a symlink: removing it removes the link, so only its
+/// parent is resolved.
+pub fn ensure_inside(dir: &Path, path: &Path) -> Result<()> {
+    let dir = dir
+        .canonicalize()
+        .with_context(|| format!("Resolving {}", dir.display()))?;
+    let resolved = match (path.parent(), path.file_name()) {
+        (Some(parent), Some(name)) => parent.canonicalize().ok().map(|p| p.join(name)),
+        _ => None,
+    };
+    match resolved {
+        Some(resolved) if resolved.starts_with(&dir) && resolved != dir => Ok(()),
+        _ => bail!(Failure::new(
+            ErrorKind::InvalidInput,
+            format!(
+                "Refusing to touch {}: it is not inside {}",
+                path.display(),
+                dir.display()
+            )
+        )),
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct StoreEntry {
+    /// Path relative to the store root, e.g. `github.com/alice/dotfiles`
+    pub key: String,
+    /// Short name accepted by every command, e.g. `dotfiles`
+    pub alias: String,
+    pub path: PathBuf,
+}
+
+impl StoreEntry {
+    /// `alias (key)` for namespaced entries, just the alias otherwise.
+    pub fn display_name(&self) -> String {
+        if self.key == self.alias {
+            self.alias.clone()
+        } else {
+            format!("{} ({})", self.alias, self.key)
+        }
+    }
+}
+
+pub struct StoreIndex {
+    pub entries: Vec<StoreEntry>,
+}
+
+impl StoreIndex {
+    /// Every repo recorded in the metadata plus unrecorded top-level directories,
+    /// sorted by alias.
+    pub fn load(root: &Path, meta: &StoreMeta) -> Result<Self> {
+        let mut entries = Vec::new();
+        let mut namespaces = BTreeSet::new();
+        for (key, repo) in &meta.repos {
+            // A hand-edited meta.yml must not point commands outside the store
+            if RepoName::key(key).is_err() {
+                continue;
+            }
+            let path = root.join(key);
+            if !path.exists() && !crate::symlink_exists(&path) {
+                continue;
+            }
+            if let Some((top, _)) = key.split_once('/') {
+                namespaces.insert(top.to_string());
+            }
+            let alias = repo
+                .alias
+                .clone()
+                .unwrap_or_else(|| key.rsplit('/').next().unwrap_or(key).to_string());
+            entries.push(StoreEntry {
+                key: key.clone(),
+                alias,
+                path,
+            });
+        }
+
+        for entry in fs::read_dir(root).with_context(|| format!("Reading {}", root.display()))? {
+            let entry = entry?;
+            let path = entry.path();
+            if !path.is_dir() {
+                continue;
+            }
+            let name = match path.file_name().and_then(|s| s.to_str()) {
+                Some(s) if !s.starts_with('.') => s.to_string(),
+                _ => continue,
+            };
+            if meta.repos.contains_key(&name) || namespaces.contains(&name) {
+                continue;
+            }
+            entries.push(StoreEntry {
+                key: name.clone(),
+                alias: name,
+                path,
+            });
+        }
+
+        entries.sort_by(|a, b| (&a.alias, &a.key).cmp(&(&b.alias, &b.key)));
+        Ok(StoreIndex { entries })
+    }
+
+    /// Look a repo up by full identity or alias.
+    pub fn find(&self, name: &str) -> Result<Option<&StoreEntry>> {
+        let name = name.trim_end_matches('/');
+        if let Some(entry) = self.entries.iter().find(|e| e.key == name) {
+            return Ok(Some(entry));
+        }
+        let matches: Vec<&StoreEntry> = self.entries.iter().filter(|e| e.alias == name).collect();
+        match matches.as_slice() {
+            [] => Ok(None),
+            [only] => Ok(Some(*only)),
+            many => bail!(Failure::new(
+                ErrorKind::InvalidInput,
+                format!(
+                    "{} matches several repos; use the full name: {}",
+                    name,
+                    many.iter()
+                        .map(|e| e.key.as_str())
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                )
+            )),
+        }
+    }
+
+    /// Entries matching any of `patterns`, each a name or a `*`/`?` glob over
+    /// aliases and full names. Every pattern has to match something.
+    pub fn select(&self, patterns: &[String]) -> Result<Vec<StoreEntry>> {
+        let mut chosen = BTreeSet::new();
+        let mut unknown = Vec::new();
+        for pattern in patterns {
+            if pattern.contains(['*', '?']) {
+                let mut matched = false;
+                for e in &self.entries {
+                    if glob_match(pattern, &e.alias) || glob_match(pattern, &e.key) {
+                        chosen.insert(e.key.as_str());
+                        matched = true;
+                    }
+                }
+                if !matched {
+                    unknown.push(pattern.as_str());
+                }
+            } else {
+                match self.find(pattern)? {
+                    Some(e) => {
+                        chosen.insert(e.key.as_str());
+                    }
+                    None => unknown.push(pattern.as_str()),
+                }
+            }
+        }
+        if !unknown.is_empty() {
+            bail!(Failure::new(
+                ErrorKind::NotFound,
+                format!(
+                    "No installed repository matches {}; see dothub list",
+                    unknown.join(", ")
+                )
+            ));
+        }
+        Ok(self
+            .entries
+            .iter()
+            .filter(|e| chosen.contains(e.key.as_str()))
+            .cloned()
+            .collect())
+    }
+
+    pub fn resolve(&self, name: &str) -> Result<&StoreEntry> {
+        self.find(name)?.ok_or_else(|| {
+            Failure::new(
+                ErrorKind::NotFound,
+                format!("Repository not found: {}", name),
+            )
+            .into()
+        })
+    }
+}
+
+/// Owner-qualified identity of a remote git URL, e.g. `github.com/alice/dotfiles`
+/// for both `https://github.com/alice/dotfiles.git` and `git@github.com:alice/dotfiles`.
+/// Local paths and `file://` URLs have none.
+pub fn repo_identity(url: &str) -> Option<String> {
+    let (host, path) = if let Ok(parsed) = url::Url::parse(url) {
+        let host = parsed.host_str()?.to_lowercase();
+        (host, parsed.path().to_string())
+    } else {
+        // scp-like syntax: [user@]host:owner/repo
+        let (head, path) = url.split_once(':')?;
+        let host = head.rsplit('@').next()?.to_lowercase();
+        if host.is_empty() || host.contains('/') {
+            return None;
+        }
+        (host, path.to_string())
+    };
+
+    let path = path.trim_matches('/');
+    let path = path.strip_suffix(".git").unwrap_or(path);
+    let segments: Vec<&str> = path
+        .split('/')
+        .filter(|s| !s.is_empty() && *s != "." && *s != "..")
+        .collect();
+    if segments.is_empty() {
+        return None;
+    }
+    Some(format!("{}/{}", host, segments.join("/")))
+}
+
+/// Whether two source URLs point at the same repo.
+pub fn same_remote(a: &str, b: &str) -> bool {
+    match (repo_identity(a), repo_identity(b)) {
+        (Some(x), Some(y)) => x.eq_ignore_ascii_case(&y),
+        _ => {
+            let norm = |s: &str| s.trim_end_matches('/').trim_end_matches(".git").to_string();
+            norm(a) == norm(b)
+        }
+    }
+}
+
+/// Remove empty namespace directories left behind under the store root.
+pub fn prune_empty_parents(root: &Path, path: &Path) {
+    let mut dir = path.parent();
+    while let Some(d) = dir {
+        if d == root || !d.starts_with(root) || fs::remove_dir(d).is_err() {
+            break;
+        }
+        dir = d.parent();
+    }
+}
+
+/// Shell-style wildcard match where `*` may also span `/`.
+fn glob_match(pattern: &str, text: &str) -> bool {
+    let (p, t): (Vec<char>, Vec<char>) = (pattern.chars().collect(), text.chars().collect());
+    let (mut pi, mut ti) = (0, 0);
+    // Position of the last `*` and the text index it currently absorbs up to
+    let mut star: Option<(usize, usize)> = None;
+    while ti < t.len() {
+        if pi < p.len() && (p[pi] == '?' || p[pi] == t[ti]) {
+            pi += 1;
+            ti += 1;
+        } else if pi < p.len() && p[pi] == '*' {
+            star = Some((pi, ti));
+            pi += 1;
+        } else if let Some((sp, st)) = star {
+            pi = sp + 1;
+            ti = st + 1;
+            star = Some((sp, st + 1));
+        } else {
+            return false;
+        }
+    }
+    p[pi..].iter().all(|&c| c == '*')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::output::kind_of;
+    use std::env;
+
+    fn rejected(result: Result<RepoName>) -> String {
+        let err = result.expect_err("name should be rejected");
+        assert_eq!(kind_of(&err), ErrorKind::InvalidInput);
+        err.to_string()
+    }
+
+    #[test]
+    fn accepts_keys_and_aliases() {
+        assert_eq!(
+            &*RepoName::key("github.com/alice/dotfiles").unwrap(),
+            "github.com/alice/dotfiles"
+        );
+        assert_eq!(&*RepoName::key("nvim/").unwrap(), "nvim");
+        assert_eq!(
+            &*RepoName::key("github.com/alice/.dotfiles").unwrap(),
+            "github.com/alice/.dotfiles"
+        );
+        assert_eq!(&*RepoName::alias(".dotfiles").unwrap(), ".dotfiles");
+        assert_eq!(&*RepoName::alias("my-bar").unwrap(), "my-bar");
+    }
+
+    #[test]
+    fn rejects_empty_names() {
+        assert!(rejected(RepoName::key("")).contains("must not be empty"));
+        assert!(rejected(RepoName::key("/")).contains("must not be empty"));
+        assert!(rejected(RepoName::alias("")).contains("must not be empty"));
+        assert!(rejected(RepoName::key("github.com//dotfiles")).contains("empty component"));
+    }
+
+    #[test]
+    fn rejects_parent_and_current_dir() {
+        for name in ["..", "../..", "github.com/../..", "a/./b", "."] {
+            assert!(
+                rejected(RepoName::key(name)).contains(". or .."),
+                "{}",
+                name
+            );
+            assert!(
+                rejected(RepoName::alias(name)).contains(". or .."),
+                "{}",
+                name
+            );
+        }
+    }
+
+    #[test]
+    fn rejects_absolute_names() {
+        for name in ["/etc", "/tmp/x/"] {
+            assert!(
+                rejected(RepoName::key(name)).contains("absolute"),
+                "{}",
+                name
+            );
+            assert!(
+                rejected(RepoName::alias(name)).contains("absolute"),
+                "{}",
+                name
+            );
+        }
+    }
+
+    #[test]
+    fn rejects_separators_in_aliases() {
+        assert!(rejected(RepoName::alias("alice/dotfiles")).contains("must not contain /"));
+        assert!(rejected(RepoName::alias("a\\b")).contains("\\"));
+        assert!(rejected(RepoName::key("a\\..\\b")).contains("\\"));
+        assert!(rejected(RepoName::key("a\0b")).contains("NUL"));
+    }
+
+    #[test]
+    fn rejects_hidden_top_level_keys() {
+        assert!(rejected(RepoName::key(".dothub")).contains("dot"));
+        assert!(rejected(RepoName::key(".dothub/meta.yml")).contains("dot"));
+        assert!(rejected(RepoName::key(".dotfiles")).contains("dot"));
+    }
+
+    #[test]
+    fn rejects_the_state_dir_as_alias() {
+        assert!(rejected(RepoName::alias(".dothub")).contains("reserved"));
+    }
+
+    #[test]
+    fn parses_command_line_names_for_lookup() {
+        assert!("alice/dotfiles".parse::<RepoName>().is_ok());
+        // Aliases of repos such as github.com/alice/.dotfiles
+        assert_eq!(&*".dotfiles".parse::<RepoName>().unwrap(), ".dotfiles");
+        assert!("github.com/alice/.dotfiles/".parse::<RepoName>().is_ok());
+        assert!("../..".parse::<RepoName>().is_err());
+        assert!("/etc".parse::<RepoName>().is_err());
+    }
+
+    #[test]
+    fn confines_paths_to_the_store() {
+        let base = env::temp_dir().join(format!("dothub-store-test-{}", std::process::id()));
+        let store = base.join("store");
+        let outside = base.join("outside");
+        fs::create_dir_all(store.join("github.com/alice/dotfiles")).unwrap();
+        fs::create_dir_all(&outside).unwrap();
+        #[cfg(unix)]
+        std::os::unix::fs::symlink(&outside, store.join("escape")).unwrap();
+
+        assert!(ensure_inside(&store, &store.join("github.com/alice/dotfiles")).is_ok());
+        assert!(ensure_inside(&store, &store).is_err());
+        assert!(ensure_inside(&store, &store.join("..")).is_err());
+        assert!(ensure_inside(&store, &store.join("github.com/../../outside")).is_err());
+        assert!(ensure_inside(&store, &outside).is_err());
+        #[cfg(unix)]
+        {
+            // The link itself is inside and may be removed; what it points at is not
+            assert!(ensure_inside(&store, &store.join("escape")).is_ok());
+            assert!(ensure_inside(&store, &store.join("escape/file")).is_err());
+        }
+        fs::remove_dir_all(&base).unwrap();
+    }
+
+    #[test]
+    fn glob_star_matches_any_run_including_slashes() {
+        assert!(glob_match("*", "nvim"));
+        assert!(glob_match("*", ""));
+        assert!(glob_match("*dot*", "github.com/alice/dotfiles"));
+        assert!(glob_match(
+            "github.com/*/dotfiles",
+            "github.com/alice/dotfiles"
+        ));
+        assert!(glob_match("a*b*c", "axxbyyc"));
+    }
+
+    #[test]
+    fn glob_question_mark_matches_one_character() {
+        assert!(glob_match("nvi?", "nvim"));
+        assert!(glob_match("??", "ab"));
+        assert!(!glob_match("?", ""));
+        assert!(!glob_match("nvim?", "nvim"));
+    }
+
+    #[test]
+    fn glob_trailing_star_matches_prefixes() {
+        assert!(glob_match("github.com/*", "github.com/alice/dotfiles"));
+        assert!(glob_match("nvim*", "nvim"));
+        assert!(glob_match("nvim**", "nvim-config"));
+        assert!(!glob_match("nvim*", "my-nvim"));
+    }
+
+    #[test]
+    fn glob_rejects_non_matching_patterns() {
+        assert!(!glob_match("nvim", "nvim-config"));
+        assert!(!glob_match("nvim", "nvi"));
+        assert!(!glob_match("*.yml", "config.yaml"));
+        assert!(!glob_match("a*b", "acbd"));
+        assert!(!glob_match("", "nvim"));
+    }
+}
@@ -0,0 +1,305 @@
+//! `dothub sync`: converge the store and ~/.config links on dothub.yml.
+
+use crate::config::{Config, DeclaredRepo, LinkMode};
+use crate::meta::{CloneStrategy, LocalChanges, TrustPolicy};
+use crate::store::StoreIndex;
+use crate::{
+    cmd_link, cmd_pin, cmd_remove, cmd_unpin, derive_repo_name, describe_install, dothub_dir,
+    ensure_store_dir, install_repo, load_store, local, progress, store, trust,
+};
+use anyhow::{bail, Context, Result};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+enum Step {
+    Install {
+        name: String,
+        spec: String,
+        clone: CloneStrategy,
+        trust: TrustPolicy,
+    },
+    Pin {
+        name: String,
+        git_ref: String,
+    },
+    Unpin {
+        name: String,
+    },
+    Trust {
+        name: String,
+        policy: TrustPolicy,
+    },
+    LocalChanges {
+        name: String,
+        strategy: Option<LocalChanges>,
+    },
+    Remove {
+        name: String,
+    },
+    Link {
+        name: String,
+        path: Option<String>,
+        target: String,
+        backup: bool,
+    },
+}
+
+impl std::fmt::Display for Step {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Step::Install { name, spec, .. } => write!(f, "+ install {} from {}", name, spec),
+            Step::Pin { name, git_ref } => write!(f, "~ pin {} at {}", name, git_ref),
+            Step::Unpin { name } => write!(f, "~ unpin {}", name),
+            Step::Trust { name, policy } if policy.enforced() => {
+                write!(f, "~ require signed commits for {}", name)
+            }
+            Step::Trust { name, .. } => write!(f, "~ accept unsigned commits for {}", name),
+            Step::LocalChanges {
+                name,
+                strategy: Some(s),
+            } => write!(f, "~ use {} for local changes in {}", s.name(), name),
+            Step::LocalChanges { name, .. } => {
+                write!(f, "~ use the default for local changes in {}", name)
+            }
+            Step::Remove { name } => write!(f, "- remove {}", name),
+            Step::Link {
+                name, path, target, ..
+            } => match path {
+                Some(p) => write!(f, "> link ~/.config/{} -> {}/{}", target, name, p),
+                None => write!(f, "> link ~/.config/{} -> {}", target, name),
+            },
+        }
+    }
+}
+
+pub fn cmd_sync(file: Option<&Path>, prune: bool, dry_run: bool, jobs: usize) -> Result<()> {
+    ensure_store_dir()?;
+    let path = match file {
+        Some(p) => p.to_path_buf(),
+        None => crate::config::default_path()?,
+    };
+    if !path.exists() {
+        bail!(
+            "No declarative config at {}; create it or pass --locked to sync from dothub.lock",
+            path.display()
+        );
+    }
+    let config = Config::load(&path)?;
+    let (steps, extra) = plan(&config, prune)?;
+
+    if steps.is_empty() {
+        println!("Everything matches {}.", path.display());
+    } else {
+        println!("Plan for {}:", path.display());
+        for step in &steps {
+            println!("  {}", step);
+        }
+    }
+    if !extra.is_empty() {
+        println!(
+            "Not declared (pass --prune to remove): {}",
+            extra.into_iter().collect::<Vec<_>>().join(", ")
+        );
+    }
+    if dry_run || steps.is_empty() {
+        return Ok(());
+    }
+
+    // Clones are independent of each other, so run them together first;
+    // pins and links may depend on them
+    let mut installs = Vec::new();
+    let mut rest = Vec::new();
+    for step in &steps {
+        match step {
+            Step::Install {
+                name,
+                spec,
+                clone,
+                trust,
+            } => installs.push((name.clone(), (name.as_str(), spec.as_str(), clone, trust))),
+            other => rest.push(other),
+        }
+    }
+    let results = progress::run(
+        jobs,
+        &installs,
+        |(name, spec, clone, trust), status| {
+            install_repo(spec, Some(name), clone, trust, false, status)
+        },
+        describe_install,
+    );
+    let mut failed = results.iter().filter(|r| r.is_err()).count();
+
+    for step in rest {
+        let result = match step {
+            Step::Install { .. } => continue,
+            Step::Pin { name, git_ref } => cmd_pin(name, git_ref),
+            Step::Unpin { name } => cmd_unpin(name),
+            Step::Trust { name, policy } => {
+                trust::cmd_trust(name, policy.clone(), !policy.enforced())
+            }
+            Step::LocalChanges { name, strategy } => {
+                local::cmd_local_changes(name, *strategy, strategy.is_none())
+            }
+            Step::Remove { name } => cmd_remove(name, true).map(|_| ()),
+            Step::Link {
+                name,
+                path,
+                target,
+                backup,
+            } => cmd_link(name, path.as_deref(), target, *backup).map(|_| ()),
+        };
+        if let Err(e) = result {
+            eprintln!("{} failed: {:#}", step, e);
+            failed += 1;
+        }
+    }
+    if failed > 0 {
+        bail!("{} of {} steps failed", failed, steps.len());
+    }
+    println!("Synced with {}.", path.display());
+    Ok(())
+}
+
+/// Alias and store key of a declared repo, and whether it is installed.
+pub fn resolve_declared(index: &StoreIndex, repo: &DeclaredRepo) -> (String, String, bool) {
+    let alias = repo
+        .name
+        .clone()
+        .unwrap_or_else(|| derive_repo_name(&repo.url));
+    let key = store::repo_identity(&repo.url).unwrap_or_else(|| alias.clone());
+    // Older stores keep repos directly under their short name
+    let existing = index
+        .entries
+        .iter()
+        .find(|e| e.key == key || (e.key == e.alias && e.alias == alias));
+    match existing {
+        Some(e) => (alias, e.key.clone(), true),
+        None => (alias, key, false),
+    }
+}
+
+/// Steps needed to match `config`, plus installed repos it does not mention
+/// that are being kept.
+fn plan(config: &Config, prune: bool) -> Result<(Vec<Step>, BTreeSet<String>)> {
+    let root = dothub_dir();
+    let (meta, index) = load_store()?;
+    // Alias -> store key of every declared repo, so links can use either
+    let mut declared: BTreeMap<String, String> = BTreeMap::new();
+    let mut declared_keys = BTreeSet::new();
+    let mut steps = Vec::new();
+
+    for repo in &config.repos {
+        let (alias, key, installed) = resolve_declared(&index, repo);
+        if declared.insert(alias.clone(), key.clone()).is_some() {
+            bail!("{} is declared more than once", alias);
+        }
+        declared_keys.insert(key.clone());
+
+        if !installed {
+            let spec = match &repo.git_ref {
+                Some(r) => format!("{}@{}", repo.url, r),
+                None => repo.url.clone(),
+            };
+            steps.push(Step::Install {
+                name: alias.clone(),
+                spec,
+                clone: repo.clone.clone(),
+                trust: repo.trust.clone(),
+            });
+            if repo.on_local_changes.is_some() {
+                steps.push(Step::LocalChanges {
+                    name: alias,
+                    strategy: repo.on_local_changes,
+                });
+            }
+            continue;
+        }
+        let current_trust = meta.repo(&key).map(|m| m.trust.clone()).unwrap_or_default();
+        if current_trust != repo.trust {
+            steps.push(Step::Trust {
+                name: key.clone(),
+                policy: repo.trust.clone(),
+            });
+        }
+        let current_strategy = meta.repo(&key).and_then(|m| m.on_local_changes);
+        if current_strategy != repo.on_local_changes {
+            steps.push(Step::LocalChanges {
+                name: key.clone(),
+                strategy: repo.on_local_changes,
+            });
+        }
+        let current_pin = meta.repo(&key).and_then(|m| m.pin.clone());
+        match (&repo.git_ref, current_pin) {
+            (Some(want), Some(have)) if *want == have => {}
+            (Some(want), _) => steps.push(Step::Pin {
+                name: key,
+                git_ref: want.clone(),
+            }),
+            (None, Some(_)) => steps.push(Step::Unpin { name: key }),
+            (None, None) => {}
+        }
+    }
+
+    // A repo a link takes its files from is kept even when it is not
+    // declared, so --prune never trashes the source of a link
+    let mut link_keys = Vec::with_capacity(config.links.len());
+    for link in &config.links {
+        let key = match declared.get(&link.source) {
+            Some(key) => key.clone(),
+            None if declared_keys.contains(&link.source) => link.source.clone(),
+            None => match index.find(&link.source)? {
+                Some(entry) => entry.key.clone(),
+                None => bail!(
+                    "Link to ~/.config/{} uses undeclared repo {}",
+                    link.target,
+                    link.source
+                ),
+            },
+        };
+        link_keys.push(key);
+    }
+
+    let mut extra = BTreeSet::new();
+    for entry in &index.entries {
+        if declared_keys.contains(&entry.key) || link_keys.contains(&entry.key) {
+            continue;
+        }
+        if prune {
+            steps.push(Step::Remove {
+                name: entry.key.clone(),
+            });
+        } else {
+            extra.insert(entry.display_name());
+        }
+    }
+
+    let config_dir = dirs::home_dir()
+        .context("Unable to determine home directory")?
+        .join(".config");
+    for (link, key) in config.links.iter().zip(link_keys) {
+        let mut want = root.join(&key);
+        if let Some(p) = &link.path {
+            want.push(p.trim_matches('/'));
+        }
+        if !link_points_to(&config_dir.join(&link.target), &want) {
+            steps.push(Step::Link {
+                name: key,
+                path: link.path.clone(),
+                target: link.target.clone(),
+                backup: link.mode == LinkMode::Backup,
+            });
+        }
+    }
+
+    Ok((steps, extra))
+}
+
+fn link_points_to(target: &Path, want: &Path) -> bool {
+    let Ok(dest) = fs::read_link(target) else {
+        return false;
+    };
+    let resolve = |p: &Path| -> PathBuf { p.canonicalize().unwrap_or_else(|_| p.to_path_buf()) };
+    resolve(&dest) == resolve(want)
+}
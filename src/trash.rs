@@ -0,0 +1,288 @@
+//! The store's trash: `dothub remove` moves repos to
+//! `<store>/.dothub/trash/<id>/` together with their metadata and update
+//! history, where `dothub trash restore` finds them again until they expire.
+
+use crate::config::Config;
+use crate::list::{dir_size, human_size};
+use crate::meta::{state_dir, RepoMeta, StoreMeta};
+use crate::output::{self, ErrorKind, Failure};
+use crate::store::{self, RepoName, StoreEntry};
+use crate::{
+    confirm, dothub_dir, ensure_store_dir, history, load_store, styled_table, symlink_exists,
+};
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const TRASH_DIR: &str = "trash";
+const ENTRY_FILE: &str = "entry.yml";
+const HISTORY_FILE: &str = "history.yml";
+/// The repo itself, inside its entry directory
+const CONTENT_DIR: &str = "repo";
+
+/// A removed repo and what is needed to put it back.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TrashEntry {
+    /// Directory name under the trash, e.g. `1792343969-dotfiles`
+    #[serde(skip_deserializing)]
+    pub id: String,
+    pub key: String,
+    pub alias: String,
+    /// Unix seconds
+    pub removed: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub meta: Option<RepoMeta>,
+}
+
+fn trash_dir(root: &Path) -> PathBuf {
+    state_dir(root).join(TRASH_DIR)
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+/// `settings.trash_days` from dothub.yml; 0 keeps entries until emptied.
+fn keep_days() -> u64 {
+    Config::load_or_default()
+        .map(|c| c.settings.trash_days)
+        .unwrap_or_else(|_| Config::default().settings.trash_days)
+}
+
+impl TrashEntry {
+    fn dir(&self, root: &Path) -> PathBuf {
+        trash_dir(root).join(&self.id)
+    }
+
+    /// When the entry expires, if it does.
+    fn expires(&self, days: u64) -> Option<u64> {
+        (days > 0).then(|| self.removed + days * 86_400)
+    }
+
+    fn display_name(&self) -> String {
+        if self.key == self.alias {
+            self.alias.clone()
+        } else {
+            format!("{} ({})", self.alias, self.key)
+        }
+    }
+}
+
+/// Every entry in the trash, newest first. Directories without a readable
+/// entry file are left alone.
+fn entries(root: &Path) -> Result<Vec<TrashEntry>> {
+    let dir = trash_dir(root);
+    if !dir.exists() {
+        return Ok(Vec::new());
+    }
+    let mut found = Vec::new();
+    for item in fs::read_dir(&dir).with_context(|| format!("Reading {}", dir.display()))? {
+        let item = item?;
+        let Ok(text) = fs::read_to_string(item.path().join(ENTRY_FILE)) else {
+            continue;
+        };
+        let Ok(mut entry) = serde_yaml::from_str::<TrashEntry>(&text) else {
+            continue;
+        };
+        entry.id = item.file_name().to_string_lossy().into_owned();
+        found.push(entry);
+    }
+    found.sort_by(|a, b| (b.removed, &b.id).cmp(&(a.removed, &a.id)));
+    Ok(found)
+}
+
+/// Move a stored repo into the trash, with its metadata and history.
+pub fn put(root: &Path, entry: &StoreEntry, meta: Option<RepoMeta>) -> Result<TrashEntry> {
+    expire(root, keep_days())?;
+    let trash = trash_dir(root);
+    fs::create_dir_all(&trash).with_context(|| format!("Creating {}", trash.display()))?;
+    let removed = now();
+    let base = format!("{}-{}", removed, entry.alias);
+    let mut id = base.clone();
+    let mut n = 1;
+    while trash.join(&id).exists() {
+        n += 1;
+        id = format!("{}-{}", base, n);
+    }
+    let trashed = TrashEntry {
+        id,
+        key: entry.key.clone(),
+        alias: entry.alias.clone(),
+        removed,
+        meta,
+    };
+
+    let dir = trashed.dir(root);
+    fs::create_dir_all(&dir).with_context(|| format!("Creating {}", dir.display()))?;
+    let text = serde_yaml::to_string(&trashed).context("Serializing trash entry")?;
+    fs::write(dir.join(ENTRY_FILE), text)
+        .with_context(|| format!("Writing {}", dir.join(ENTRY_FILE).display()))?;
+    // Both live inside the store, so this is a rename on one filesystem
+    if let Err(e) = fs::rename(&entry.path, dir.join(CONTENT_DIR)) {
+        let _ = fs::remove_dir_all(&dir);
+        return Err(e).with_context(|| format!("Moving {} to the trash", entry.path.display()));
+    }
+    let history = history::history_path(root, &entry.key);
+    if history.exists() {
+        let _ = fs::rename(&history, dir.join(HISTORY_FILE));
+    }
+    Ok(trashed)
+}
+
+/// Delete entries older than `days`; 0 keeps everything.
+fn expire(root: &Path, days: u64) -> Result<()> {
+    let now = now();
+    for entry in entries(root)? {
+        if entry.expires(days).is_some_and(|t| t <= now) {
+            delete(root, &entry)?;
+        }
+    }
+    Ok(())
+}
+
+fn delete(root: &Path, entry: &TrashEntry) -> Result<()> {
+    let dir = entry.dir(root);
+    store::ensure_inside(&trash_dir(root), &dir)?;
+    fs::remove_dir_all(&dir).with_context(|| format!("Removing {}", dir.display()))
+}
+
+pub fn cmd_list() -> Result<()> {
+    ensure_store_dir()?;
+    let root = dothub_dir();
+    let days = keep_days();
+    expire(&root, days)?;
+    let trashed = entries(&root)?;
+    if output::structured() {
+        return output::emit(&trashed);
+    }
+    if trashed.is_empty() {
+        println!("The trash is empty.");
+        return Ok(());
+    }
+    let mut table = styled_table();
+    table.set_header(["Entry", "Repo", "Removed", "Expires", "Size"]);
+    for entry in &trashed {
+        let size = dir_size(&entry.dir(&root))
+            .map(human_size)
+            .unwrap_or_else(|_| "?".to_string());
+        let expires = entry
+            .expires(days)
+            .map(history::format_time)
+            .unwrap_or_else(|| "never".to_string());
+        table.add_row([
+            entry.id.clone(),
+            entry.display_name(),
+            history::format_time(entry.removed),
+            expires,
+            size,
+        ]);
+    }
+    println!("{}", table);
+    Ok(())
+}
+
+/// Put a trashed repo back, picked by entry id or by name (newest first).
+pub fn cmd_restore(name: &str) -> Result<()> {
+    ensure_store_dir()?;
+    let root = dothub_dir();
+    expire(&root, keep_days())?;
+    let trashed = entries(&root)?;
+    let entry = trashed
+        .iter()
+        .find(|e| e.id == name)
+        .or_else(|| trashed.iter().find(|e| e.key == name || e.alias == name))
+        .ok_or_else(|| {
+            Failure::new(
+                ErrorKind::NotFound,
+                format!("Nothing named {} in the trash; see dothub trash list", name),
+            )
+        })?;
+
+    let key = RepoName::key(&entry.key)?;
+    let dest = root.join(&key);
+    let (meta, index) = load_store()?;
+    if dest.exists() || symlink_exists(&dest) || meta.repos.contains_key(&entry.key) {
+        bail!(Failure::new(
+            ErrorKind::InvalidInput,
+            format!(
+                "{} is installed again; remove it before restoring the old copy",
+                entry.key
+            )
+        ));
+    }
+    // Like install, only a chosen alias or a top-level key has to be unique
+    let chosen = entry.key == entry.alias || entry.meta.as_ref().is_some_and(|m| m.alias.is_some());
+    if let Some(other) = index
+        .entries
+        .iter()
+        .find(|e| chosen && (e.alias == entry.alias || e.key == entry.alias))
+    {
+        bail!(Failure::new(
+            ErrorKind::InvalidInput,
+            format!("The name {} is now used by {}", entry.alias, other.key)
+        ));
+    }
+
+    let dir = entry.dir(&root);
+    if let Some(parent) = dest.parent() {
+        fs::create_dir_all(parent).with_context(|| format!("Creating {}", parent.display()))?;
+    }
+    fs::rename(dir.join(CONTENT_DIR), &dest)
+        .with_context(|| format!("Moving {} out of the trash", entry.key))?;
+    if let Some(repo) = &entry.meta {
+        StoreMeta::update(&root, |meta| {
+            meta.repos.insert(entry.key.clone(), repo.clone());
+        })?;
+    }
+    let history = dir.join(HISTORY_FILE);
+    if history.exists() {
+        let to = history::history_path(&root, &entry.key);
+        if let Some(parent) = to.parent() {
+            fs::create_dir_all(parent).with_context(|| format!("Creating {}", parent.display()))?;
+        }
+        let _ = fs::rename(&history, to);
+    }
+    delete(&root, entry)?;
+
+    if output::structured() {
+        return output::emit(entry);
+    }
+    println!("Restored {} to {}", entry.display_name(), dest.display());
+    Ok(())
+}
+
+pub fn cmd_empty(yes: bool) -> Result<()> {
+    ensure_store_dir()?;
+    let root = dothub_dir();
+    let trashed = entries(&root)?;
+    if trashed.is_empty() {
+        if output::structured() {
+            return output::emit(&trashed);
+        }
+        println!("The trash is empty.");
+        return Ok(());
+    }
+    let question = match trashed.len() {
+        1 => format!("Permanently delete {}?", trashed[0].display_name()),
+        n => format!("Permanently delete {} trashed repos?", n),
+    };
+    if !confirm(&question, yes)? {
+        bail!("Cancelled; the trash was left as it is");
+    }
+    for entry in &trashed {
+        delete(&root, entry)?;
+    }
+    if output::structured() {
+        return output::emit(&trashed);
+    }
+    match trashed.len() {
+        1 => println!("Deleted {} from the trash", trashed[0].display_name()),
+        n => println!("Deleted {} repos from the trash", n),
+    }
+    Ok(())
+}
@@ -0,0 +1,178 @@
+//! Commit signature checks for repos with a trust policy. Dotfiles often end
+//! up sourced by a shell, so some repos should only move to signed commits.
+
+use crate::meta::{StoreMeta, TrustPolicy};
+use crate::output::{ErrorKind, Failure};
+use crate::{dothub_dir, git, resolve_repo, short_commit};
+use anyhow::{bail, Context, Result};
+use std::path::Path;
+
+/// What git reports about a commit's signature.
+struct Signature {
+    /// `%G?`: G good, U good but of unknown validity, N none, B bad, ...
+    status: char,
+    key: String,
+    fingerprint: String,
+    primary_fingerprint: String,
+    signer: String,
+}
+
+impl Signature {
+    fn read(repo: &Path, commit: &str) -> Result<Self> {
+        let out = git::output(
+            repo,
+            &["log", "-1", "--format=%G?%n%GK%n%GF%n%GP%n%GS", commit],
+        )
+        .with_context(|| format!("Checking the signature of {}", short_commit(commit)))?;
+        let mut lines = out.lines().map(|l| l.trim().to_string());
+        let mut next = || lines.next().unwrap_or_default();
+        Ok(Signature {
+            status: next().chars().next().unwrap_or('N'),
+            key: next(),
+            fingerprint: next(),
+            primary_fingerprint: next(),
+            signer: next(),
+        })
+    }
+
+    fn describe_signer(&self) -> String {
+        match (self.signer.is_empty(), self.key.is_empty()) {
+            (false, false) => format!("{} (key {})", self.signer, self.key),
+            (false, true) => self.signer.clone(),
+            _ => format!("key {}", self.key),
+        }
+    }
+
+    /// `key` may be a long key id, a full fingerprint or an ssh `SHA256:` fingerprint.
+    fn matches(&self, key: &str) -> bool {
+        let want = normalize_key(key);
+        if want.is_empty() {
+            return false;
+        }
+        [&self.key, &self.fingerprint, &self.primary_fingerprint]
+            .into_iter()
+            .map(|k| normalize_key(k))
+            .filter(|k| !k.is_empty())
+            .any(|have| have == want || (want.len() >= 16 && have.ends_with(&want)))
+    }
+}
+
+fn normalize_key(key: &str) -> String {
+    let key: String = key.chars().filter(|c| !c.is_whitespace()).collect();
+    if key.starts_with("SHA256:") {
+        return key;
+    }
+    key.trim_start_matches("0x").to_uppercase()
+}
+
+/// Why `policy` refuses `commit`, or `None` when the commit may be checked out.
+pub fn rejection(repo: &Path, commit: &str, policy: &TrustPolicy) -> Result<Option<String>> {
+    if !policy.enforced() {
+        return Ok(None);
+    }
+    git::ensure_available().context("Verifying commit signatures needs git")?;
+    let sig = Signature::read(repo, commit)?;
+    let short = short_commit(commit);
+    let reason = match sig.status {
+        'G' | 'U' => None,
+        'N' => Some(format!("{} is not signed", short)),
+        'B' => Some(format!("{} has a bad signature", short)),
+        'E' => Some(format!(
+            "{} is signed by key {}, which is not in your keyring",
+            short, sig.key
+        )),
+        'X' => Some(format!("{} has an expired signature", short)),
+        'Y' => Some(format!("{} is signed by an expired key", short)),
+        'R' => Some(format!("{} is signed by a revoked key", short)),
+        other => Some(format!(
+            "{} has an unknown signature status {}",
+            short, other
+        )),
+    };
+    if reason.is_some() {
+        return Ok(reason);
+    }
+    if !policy.trusted_keys.is_empty() {
+        if policy.trusted_keys.iter().any(|k| sig.matches(k)) {
+            return Ok(None);
+        }
+        return Ok(Some(format!(
+            "{} is signed by {}, which is not a trusted key",
+            short,
+            sig.describe_signer()
+        )));
+    }
+    if sig.status == 'U' {
+        let key = if sig.fingerprint.is_empty() {
+            &sig.key
+        } else {
+            &sig.fingerprint
+        };
+        return Ok(Some(format!(
+            "{} is signed by {}, whose key is not trusted; pass --trusted-key {} to accept it",
+            short,
+            sig.describe_signer(),
+            key
+        )));
+    }
+    Ok(None)
+}
+
+/// Fail with the policy's reason when `commit` is refused.
+pub fn verify(repo: &Path, commit: &str, policy: &TrustPolicy) -> Result<()> {
+    match rejection(repo, commit, policy)? {
+        Some(reason) => bail!(Failure::new(
+            ErrorKind::Trust,
+            format!("Signature check failed: {}", reason)
+        )),
+        None => Ok(()),
+    }
+}
+
+pub fn cmd_trust(name: &str, policy: TrustPolicy, off: bool) -> Result<()> {
+    let entry = resolve_repo(name)?;
+    let root = dothub_dir();
+
+    if !off && policy.is_open() {
+        let meta = StoreMeta::load(&root)?;
+        let current = meta.repo(&entry.key).map(|m| &m.trust);
+        match current.filter(|t| t.enforced()) {
+            None => println!("{} accepts unsigned commits", entry.alias),
+            Some(t) if t.trusted_keys.is_empty() => {
+                println!("{} requires commits signed by a trusted key", entry.alias)
+            }
+            Some(t) => println!(
+                "{} requires commits signed by one of: {}",
+                entry.alias,
+                t.trusted_keys.join(", ")
+            ),
+        }
+        return Ok(());
+    }
+
+    let policy = if off { TrustPolicy::default() } else { policy };
+    if entry.path.join(".git").exists() {
+        let head = git::head_commit(&entry.path)?;
+        if let Some(reason) = rejection(&entry.path, &head, &policy)? {
+            eprintln!(
+                "Warning: the current checkout does not satisfy this policy: {}",
+                reason
+            );
+        }
+    } else if policy.enforced() {
+        bail!(
+            "{} is not a git repository; it has no commits to verify",
+            entry.alias
+        );
+    }
+    StoreMeta::update(&root, |meta| {
+        meta.repo_mut(&entry.key).trust = policy.clone()
+    })?;
+
+    if off {
+        println!("{} accepts unsigned commits again", entry.alias);
+    } else {
+        println!("Updated the trust policy of {}", entry.alias);
+    }
+    Ok(())
+}